@@ -1,11 +1,18 @@
-use std::{cmp, fmt};
+use std::{cmp, fmt, io};
 use std::io::{Read, Write, Result};
-use std::ptr;
+use crate::error::{ErrorKind, RpError, RpResult};
+use crate::proto_io::Checkpoint;
+#[cfg(feature = "bytes")]
+use bytes::{Buf, BufMut, buf::UninitSlice};
+
+/// `compact` only moves the unread bytes once this many bytes are consumed
+const COMPACT_MIN_LEN: usize = 4096;
 
 pub struct Buffer {
     data: Vec<u8>,
     read_pos: usize,
     write_pos: usize,
+    max_size: usize,
 }
 
 impl Buffer {
@@ -14,9 +21,28 @@ impl Buffer {
             data: Vec::new(),
             read_pos: 0,
             write_pos: 0,
+            max_size: usize::MAX,
+        }
+    }
+
+    /// writes which grow the data over `max_size` fail with `BufferOverMaxError`
+    pub fn with_max_size(max_size: usize) -> Buffer {
+        Buffer {
+            data: Vec::new(),
+            read_pos: 0,
+            write_pos: 0,
+            max_size,
         }
     }
 
+    pub fn get_max_size(&self) -> usize {
+        self.max_size
+    }
+
+    pub fn set_max_size(&mut self, max_size: usize) {
+        self.max_size = max_size;
+    }
+
     pub fn get_data(&self) -> &Vec<u8> {
         &self.data
     }
@@ -25,6 +51,64 @@ impl Buffer {
         self.data.len()
     }
 
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.data.reserve(additional);
+    }
+
+    /// the bytes after the read position
+    pub fn remaining(&self) -> usize {
+        self.data.len() - cmp::min(self.data.len(), self.read_pos)
+    }
+
+    pub fn as_read_slice(&self) -> &[u8] {
+        &self.data[cmp::min(self.data.len(), self.read_pos)..]
+    }
+
+    /// skip the next n bytes, nothing is skipped if less are left
+    pub fn advance(&mut self, n: usize) -> RpResult<()> {
+        ensure!(n <= self.remaining(), (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        self.read_pos += n;
+        Ok(())
+    }
+
+    /// the next len bytes without copy, nothing is consumed if less are left
+    pub fn read_bytes(&mut self, len: usize) -> RpResult<&[u8]> {
+        ensure!(len <= self.remaining(), (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        let start = self.read_pos;
+        self.read_pos += len;
+        Ok(&self.data[start..start + len])
+    }
+
+    /// fill the whole buf, nothing is consumed if less are left
+    pub fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        let data = self.read_bytes(buf.len())?;
+        buf.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// drop the consumed bytes, they are only moved when all are consumed or
+    /// the consumed part is large and not smaller than the unread part
+    pub fn compact(&mut self) {
+        if self.read_pos == 0 {
+            return;
+        }
+        if self.remaining() == 0 {
+            self.clear();
+        } else if self.read_pos >= COMPACT_MIN_LEN && self.read_pos >= self.remaining() {
+            let pos = self.read_pos;
+            self.drain(pos);
+        }
+    }
+
+    /// the read position is restored when the guard drops, unless it is committed
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, Buffer> {
+        Checkpoint::new(self)
+    }
+
     pub fn set_read_pos(&mut self, r_pos: usize) {
         self.read_pos = r_pos;
     }
@@ -41,6 +125,13 @@ impl Buffer {
         self.write_pos
     }
 
+    /// drop the data after len, the positions are kept within it
+    pub fn truncate(&mut self, len: usize) {
+        self.data.truncate(len);
+        self.read_pos = cmp::min(self.read_pos, len);
+        self.write_pos = cmp::min(self.write_pos, len);
+    }
+
     pub fn drain(&mut self, pos: usize) {
         self.read_pos = self.read_pos - cmp::min(self.read_pos, pos);
         self.write_pos = self.write_pos - cmp::min(self.write_pos, pos);
@@ -70,21 +161,8 @@ impl fmt::Debug for Buffer {
 
 impl Read for Buffer {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let left = self.data.len() - self.read_pos;
-        if left == 0 || buf.len() == 0 {
-            return Ok(0)
-        }
-
-        let read_len = if left > buf.len() {
-            buf.len()
-        } else {
-            left
-        };
-
-        unsafe {
-            ptr::copy(&self.data[self.read_pos], &mut buf[0], read_len)
-        }
-
+        let read_len = cmp::min(self.remaining(), buf.len());
+        buf[..read_len].copy_from_slice(&self.as_read_slice()[..read_len]);
         self.read_pos += read_len;
         Ok(read_len)
     }
@@ -92,23 +170,70 @@ impl Read for Buffer {
 
 impl Write for Buffer {
     fn write(&mut self, buf: &[u8]) -> Result<usize> {
-        if self.data.len() < self.write_pos + buf.len() {
-            self.data.resize(self.write_pos + buf.len(), 0)
-        }
-
-        if buf.len() == 0 {
-            return Ok(0);
+        if self.write_pos + buf.len() > self.max_size {
+            let err = RpError::from((ErrorKind::BufferOverMaxError, "the buffer over max size"));
+            return Err(io::Error::new(io::ErrorKind::Other, err));
         }
 
-        unsafe {
-            ptr::copy(&buf[0], &mut self.data[self.write_pos], buf.len());
+        let end = self.write_pos + buf.len();
+        if self.data.len() < end {
+            self.data.resize(end, 0)
         }
-
-        self.write_pos += buf.len();
+        self.data[self.write_pos..end].copy_from_slice(buf);
+        self.write_pos = end;
         Ok(buf.len())
     }
 
     fn flush(&mut self) -> Result<()> {
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(feature = "bytes")]
+impl Buf for Buffer {
+    fn remaining(&self) -> usize {
+        Buffer::remaining(self)
+    }
+
+    fn chunk(&self) -> &[u8] {
+        self.as_read_slice()
+    }
+
+    fn advance(&mut self, cnt: usize) {
+        assert!(cnt <= Buffer::remaining(self), "cannot advance past the written data");
+        self.read_pos += cnt;
+    }
+}
+
+/// the writable bytes are limited by `max_size`, writing over the written data
+/// overwrites it like `Write` does
+#[cfg(feature = "bytes")]
+unsafe impl BufMut for Buffer {
+    fn remaining_mut(&self) -> usize {
+        self.max_size.saturating_sub(self.write_pos)
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        assert!(cnt <= self.remaining_mut(), "cannot advance past the max size");
+        let pos = self.write_pos + cnt;
+        if self.data.len() < pos {
+            // the caller initialized these bytes through `chunk_mut`
+            self.data.set_len(pos);
+        }
+        self.write_pos = pos;
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        let pos = self.write_pos;
+        if pos < self.data.len() {
+            return UninitSlice::new(&mut self.data[pos..]);
+        }
+        if pos > self.data.len() {
+            self.data.resize(pos, 0);
+        }
+        if self.data.capacity() == self.data.len() {
+            self.data.reserve(64);
+        }
+        UninitSlice::uninit(self.data.spare_capacity_mut())
+    }
+}
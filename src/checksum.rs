@@ -0,0 +1,74 @@
+use crate::error::*;
+use crate::frame::{FRAME_FLAG_CRC32, FRAME_FLAG_XXHASH};
+
+/// the checksum trailer is a u32
+pub const CHECKSUM_LEN: usize = 4;
+
+/// the algorithm of the frame trailer, the receiver follows the frame flags so
+/// it only needs the matching cargo feature
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Checksum {
+    None,
+    /// needs the `crc32` feature
+    Crc32,
+    /// xxh32 with seed 0, needs the `xxhash` feature
+    XxHash32,
+}
+
+impl Checksum {
+    /// the frame flag which marks the trailer
+    pub fn get_flag(&self) -> u8 {
+        match *self {
+            Checksum::None => 0,
+            Checksum::Crc32 => FRAME_FLAG_CRC32,
+            Checksum::XxHash32 => FRAME_FLAG_XXHASH,
+        }
+    }
+
+    /// a frame can't carry both trailers
+    pub fn from_flags(flags: u8) -> RpResult<Checksum> {
+        match (flags & FRAME_FLAG_CRC32 != 0, flags & FRAME_FLAG_XXHASH != 0) {
+            (true, true) => fail!((ErrorKind::ChecksumError, "frame has both crc32 and xxhash flags")),
+            (true, false) => Ok(Checksum::Crc32),
+            (false, true) => Ok(Checksum::XxHash32),
+            (false, false) => Ok(Checksum::None),
+        }
+    }
+}
+
+/// the checksum of the parts as if they were one slice
+pub fn compute_checksum(checksum: Checksum, parts: &[&[u8]]) -> RpResult<u32> {
+    match checksum {
+        Checksum::None => Ok(0),
+        Checksum::Crc32 => compute_crc32(parts),
+        Checksum::XxHash32 => compute_xxhash(parts),
+    }
+}
+
+#[cfg(feature = "crc32")]
+fn compute_crc32(parts: &[&[u8]]) -> RpResult<u32> {
+    let mut hasher = crc32fast::Hasher::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    Ok(hasher.finalize())
+}
+
+#[cfg(not(feature = "crc32"))]
+fn compute_crc32(_parts: &[&[u8]]) -> RpResult<u32> {
+    fail!((ErrorKind::ChecksumError, "crc32 needs the crc32 feature"))
+}
+
+#[cfg(feature = "xxhash")]
+fn compute_xxhash(parts: &[&[u8]]) -> RpResult<u32> {
+    let mut hasher = xxhash_rust::xxh32::Xxh32::new(0);
+    for part in parts {
+        hasher.update(part);
+    }
+    Ok(hasher.digest())
+}
+
+#[cfg(not(feature = "xxhash"))]
+fn compute_xxhash(_parts: &[&[u8]]) -> RpResult<u32> {
+    fail!((ErrorKind::ChecksumError, "xxhash needs the xxhash feature"))
+}
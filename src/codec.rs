@@ -0,0 +1,83 @@
+use std::sync::Arc;
+use bytes::BytesMut;
+use tokio_util::codec::{Decoder, Encoder};
+use crate::value::*;
+use crate::error::*;
+use crate::config::Config;
+use crate::frame::*;
+
+/// a tokio codec which frames `(name, args)` messages with the frame header
+pub struct ProtoCodec {
+    config: Arc<Config>,
+    seq: Option<u32>,
+    session: FrameSession,
+}
+
+impl ProtoCodec {
+    pub fn new(config: Arc<Config>) -> ProtoCodec {
+        ProtoCodec {
+            config,
+            seq: None,
+            session: FrameSession::new(),
+        }
+    }
+
+    /// number the outgoing frames from `seq`
+    pub fn with_seq(config: Arc<Config>, seq: u32) -> ProtoCodec {
+        ProtoCodec {
+            config,
+            seq: Some(seq),
+            session: FrameSession::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &Arc<Config> {
+        &self.config
+    }
+
+    pub fn get_session(&self) -> &FrameSession {
+        &self.session
+    }
+
+    /// change the framing once the peer agreed, such as turning the checksum on
+    /// or sealing with the key of the connection
+    pub fn get_session_mut(&mut self) -> &mut FrameSession {
+        &mut self.session
+    }
+}
+
+impl Decoder for ProtoCodec {
+    type Item = (String, Vec<Value>);
+    type Error = RpError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> RpResult<Option<(String, Vec<Value>)>> {
+        match check_frame_bytes(&src[..], &self.config)? {
+            FrameState::NeedMore(len) => {
+                src.reserve(len);
+                Ok(None)
+            }
+            FrameState::Ready(header) => {
+                let frame = src.split_to(header.frame_len());
+                let result = decode_frame_body(&self.config, &mut self.session, &header, &frame[header.header_len()..])?;
+                Ok(Some(result))
+            }
+        }
+    }
+}
+
+impl Encoder<(String, Vec<Value>)> for ProtoCodec {
+    type Error = RpError;
+
+    fn encode(&mut self, item: (String, Vec<Value>), dst: &mut BytesMut) -> RpResult<()> {
+        let (name, infos) = item;
+        let len = dst.len();
+        if let Err(err) = encode_frame_with(dst, &self.config, &mut self.session, &name, infos, self.seq) {
+            dst.truncate(len);
+            return Err(err);
+        }
+        if let Some(seq) = self.seq {
+            self.seq = Some(seq.wrapping_add(1));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+use crate::error::*;
+use crate::frame::{FRAME_FLAG_LZ4, FRAME_FLAG_ZSTD};
+
+/// the compressed payload starts with the u32 length of the original payload
+const COMPRESS_LEN_PREFIX: usize = 4;
+
+/// the algorithm is chosen by the sender, the receiver follows the frame flags so
+/// it only needs the matching cargo feature
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Compression {
+    None,
+    /// needs the `lz4` feature
+    Lz4,
+    /// needs the `zstd` feature, with the compression level
+    Zstd(i32),
+}
+
+impl Compression {
+    /// the frame flag which marks the compressed payload
+    pub fn get_flag(&self) -> u8 {
+        match *self {
+            Compression::None => 0,
+            Compression::Lz4 => FRAME_FLAG_LZ4,
+            Compression::Zstd(_) => FRAME_FLAG_ZSTD,
+        }
+    }
+
+    /// a frame can't be compressed by both algorithms
+    pub fn from_flags(flags: u8) -> RpResult<Compression> {
+        match (flags & FRAME_FLAG_LZ4 != 0, flags & FRAME_FLAG_ZSTD != 0) {
+            (true, true) => fail!((ErrorKind::CompressError, "frame has both lz4 and zstd flags")),
+            (true, false) => Ok(Compression::Lz4),
+            (false, true) => Ok(Compression::Zstd(0)),
+            (false, false) => Ok(Compression::None),
+        }
+    }
+}
+
+pub fn compress(compression: Compression, data: &[u8]) -> RpResult<Vec<u8>> {
+    let mut result = (data.len() as u32).to_le_bytes().to_vec();
+    match compression {
+        Compression::None => result.extend_from_slice(data),
+        Compression::Lz4 => result.extend_from_slice(&compress_lz4(data)?),
+        Compression::Zstd(level) => result.extend_from_slice(&compress_zstd(data, level)?),
+    }
+    Ok(result)
+}
+
+/// the original payload may not be longer than `max_len`
+pub fn decompress(compression: Compression, data: &[u8], max_len: usize) -> RpResult<Vec<u8>> {
+    ensure!(data.len() >= COMPRESS_LEN_PREFIX,
+            (ErrorKind::CompressError, "compressed payload is too short"));
+    let mut len = [0; COMPRESS_LEN_PREFIX];
+    len.copy_from_slice(&data[..COMPRESS_LEN_PREFIX]);
+    let len = u32::from_le_bytes(len) as usize;
+    ensure!(len <= max_len, (ErrorKind::CompressError, "decompressed payload over max len", len.to_string()));
+
+    let data = &data[COMPRESS_LEN_PREFIX..];
+    let result = match compression {
+        Compression::None => data.to_vec(),
+        Compression::Lz4 => decompress_lz4(data, len)?,
+        Compression::Zstd(_) => decompress_zstd(data, len)?,
+    };
+    ensure!(result.len() == len, (ErrorKind::CompressError, "decompressed payload length not match"));
+    Ok(result)
+}
+
+#[cfg(feature = "lz4")]
+fn compress_lz4(data: &[u8]) -> RpResult<Vec<u8>> {
+    Ok(lz4_flex::block::compress(data))
+}
+
+#[cfg(not(feature = "lz4"))]
+fn compress_lz4(_data: &[u8]) -> RpResult<Vec<u8>> {
+    fail!((ErrorKind::CompressError, "lz4 needs the lz4 feature"))
+}
+
+#[cfg(feature = "lz4")]
+fn decompress_lz4(data: &[u8], len: usize) -> RpResult<Vec<u8>> {
+    match lz4_flex::block::decompress(data, len) {
+        Ok(result) => Ok(result),
+        Err(err) => fail!((ErrorKind::CompressError, "lz4 decompress failed", err.to_string())),
+    }
+}
+
+#[cfg(not(feature = "lz4"))]
+fn decompress_lz4(_data: &[u8], _len: usize) -> RpResult<Vec<u8>> {
+    fail!((ErrorKind::CompressError, "lz4 needs the lz4 feature"))
+}
+
+#[cfg(feature = "zstd")]
+fn compress_zstd(data: &[u8], level: i32) -> RpResult<Vec<u8>> {
+    match zstd::bulk::compress(data, level) {
+        Ok(result) => Ok(result),
+        Err(err) => fail!((ErrorKind::CompressError, "zstd compress failed", err.to_string())),
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn compress_zstd(_data: &[u8], _level: i32) -> RpResult<Vec<u8>> {
+    fail!((ErrorKind::CompressError, "zstd needs the zstd feature"))
+}
+
+#[cfg(feature = "zstd")]
+fn decompress_zstd(data: &[u8], len: usize) -> RpResult<Vec<u8>> {
+    match zstd::bulk::decompress(data, len) {
+        Ok(result) => Ok(result),
+        Err(err) => fail!((ErrorKind::CompressError, "zstd decompress failed", err.to_string())),
+    }
+}
+
+#[cfg(not(feature = "zstd"))]
+fn decompress_zstd(_data: &[u8], _len: usize) -> RpResult<Vec<u8>> {
+    fail!((ErrorKind::CompressError, "zstd needs the zstd feature"))
+}
@@ -1,10 +1,17 @@
 use rustc_serialize::json;
 use std::collections::HashMap;
+use crate::value::*;
+
+/// a peer may not send a larger frame, or a larger message on a stream, than this
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
 
 #[derive(RustcDecodable, Debug)]
 pub struct Field {
     pub index: u16,
     pub pattern: String,
+    pub required: Option<bool>,
+    /// the default value written as a string, parsed by the pattern
+    pub default: Option<String>,
 }
 
 #[derive(RustcDecodable, Debug)]
@@ -13,12 +20,66 @@ pub struct Proto {
     pub args: Vec<String>,
 }
 
+#[derive(RustcDecodable, Debug)]
+pub struct EnumDef {
+    pub pattern: String,
+    pub variants: HashMap<String, u32>,
+    /// the variant name of each value, built by `Config::add_enum`
+    value_name: Option<HashMap<u32, String>>,
+}
+
+#[derive(RustcDecodable, Debug)]
+pub struct UnionArm {
+    pub tag: u16,
+    pub pattern: String,
+}
+
+#[derive(RustcDecodable, Debug)]
+pub struct UnionDef {
+    pub arms: HashMap<String, UnionArm>,
+    /// the arm name of each tag, built by `Config::add_union`
+    tag_arm: Option<HashMap<u16, String>>,
+}
+
+#[derive(RustcDecodable, Debug)]
+pub struct StructDef {
+    pub fields: HashMap<String, Field>,
+    /// the field name of each index, built by `Config::add_struct`
+    index_field: Option<HashMap<u16, String>>,
+}
+
 #[derive(Debug)]
 pub struct Config {
     field: HashMap<String, Field>,
     proto: HashMap<String, Proto>,
+    enums: HashMap<String, EnumDef>,
+    unions: HashMap<String, UnionDef>,
+    structs: HashMap<String, StructDef>,
     index_field: HashMap<u16, String>,
     msg_proto: HashMap<String, String>,
+    omit_default: bool,
+    align: bool,
+    max_frame_len: usize,
+}
+
+fn missing_names(fields: &HashMap<String, Field>, map: &HashMap<String, Value>) -> Vec<String> {
+    let mut names: Vec<String> = fields.iter()
+        .filter(|&(name, field)| field.is_required() && !field.has_default() && !map.contains_key(name))
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.sort();
+    names
+}
+
+fn fill_default_values(config: &Config, fields: &HashMap<String, Field>, map: &mut HashMap<String, Value>) {
+    for (name, field) in fields {
+        if map.contains_key(name) {
+            continue;
+        }
+        if let Some(value) = config.get_default_value(field) {
+            map.insert(name.clone(), value);
+        }
+    }
 }
 
 impl Field {
@@ -26,6 +87,8 @@ impl Field {
         Field {
             index: 0,
             pattern: "null".to_string(),
+            required: None,
+            default: None,
         }
     }
 
@@ -37,8 +100,85 @@ impl Field {
         Field {
             index: 0,
             pattern,
+            required: None,
+            default: None,
         }
     }
+
+    pub fn is_required(&self) -> bool {
+        self.required.unwrap_or(false)
+    }
+
+    pub fn has_default(&self) -> bool {
+        self.default.is_some()
+    }
+}
+
+impl EnumDef {
+    pub fn get_variant_name(&self, value: u32) -> Option<&String> {
+        self.value_name.as_ref().and_then(|value_name| value_name.get(&value))
+    }
+
+    pub fn get_variant_value(&self, name: &String) -> Option<u32> {
+        self.variants.get(name).cloned()
+    }
+
+    fn is_valid(&self) -> bool {
+        let value_name = unwrap_or!(self.value_name.as_ref(), return false);
+        value_name.len() == self.variants.len()
+            && self.variants.values().all(|value| self.to_base_value(*value).is_some())
+    }
+
+    /// the numeric value as it is written on the wire, by the enum's base pattern
+    pub fn to_base_value(&self, value: u32) -> Option<Value> {
+        match get_type_by_name(&self.pattern) {
+            TYPE_U8 if value <= u8::MAX as u32 => Some(Value::U8(value as u8)),
+            TYPE_U16 if value <= u16::MAX as u32 => Some(Value::U16(value as u16)),
+            TYPE_U32 => Some(Value::U32(value)),
+            _ => None,
+        }
+    }
+}
+
+impl UnionDef {
+    pub fn get_arm_by_name(&self, name: &String) -> Option<&UnionArm> {
+        self.arms.get(name)
+    }
+
+    pub fn get_arm_by_tag(&self, tag: u16) -> Option<(&String, &UnionArm)> {
+        let name = unwrap_or!(self.tag_arm.as_ref().and_then(|tag_arm| tag_arm.get(&tag)), return None);
+        self.arms.get_key_value(name)
+    }
+
+    fn is_valid(&self) -> bool {
+        let tag_arm = unwrap_or!(self.tag_arm.as_ref(), return false);
+        tag_arm.len() == self.arms.len()
+    }
+}
+
+impl StructDef {
+    pub fn get_field_by_name(&self, name: &str) -> Option<&Field> {
+        self.fields.get(name)
+    }
+
+    pub fn get_field_by_index(&self, index: &u16) -> Option<(&String, &Field)> {
+        let name = unwrap_or!(self.index_field.as_ref().and_then(|index_field| index_field.get(index)), return None);
+        self.fields.get_key_value(name)
+    }
+
+    fn is_valid(&self) -> bool {
+        let index_field = unwrap_or!(self.index_field.as_ref(), return false);
+        index_field.len() == self.fields.len()
+    }
+
+    /// the required field names without default which the map doesn't contain
+    pub fn get_missing_names(&self, map: &HashMap<String, Value>) -> Vec<String> {
+        missing_names(&self.fields, map)
+    }
+
+    pub fn fill_default_values(&self, config: &Config, map: &mut HashMap<String, Value>) {
+        fill_default_values(config, &self.fields, map)
+    }
 }
 
 impl Config {
@@ -46,8 +186,14 @@ impl Config {
         Config {
             field: HashMap::new(),
             proto: HashMap::new(),
+            enums: HashMap::new(),
+            unions: HashMap::new(),
+            structs: HashMap::new(),
             index_field: HashMap::new(),
             msg_proto: HashMap::new(),
+            omit_default: false,
+            align: false,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
         }
     }
 
@@ -66,11 +212,19 @@ impl Config {
         Config {
             field,
             proto,
+            enums: HashMap::new(),
+            unions: HashMap::new(),
+            structs: HashMap::new(),
             index_field,
             msg_proto,
+            omit_default: false,
+            align: false,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
         }
     }
 
+    /// the whole config with the `field` and `proto` sections and the optional
+    /// `enum`, `union` and `struct` sections
     pub fn new_from_full_str(config: &str) -> Option<Config> {
         let info = json::Json::from_str(config);
         if info.is_err() {
@@ -87,19 +241,50 @@ impl Config {
         if field.is_err() || proto.is_err() {
             return None;
         }
-        Some(Self::new_from_map(field.ok().unwrap(), proto.ok().unwrap()))
+        let mut config = Self::new_from_map(field.ok().unwrap(), proto.ok().unwrap());
+
+        if let Some(enums) = info.find("enum") {
+            let enums: Result<HashMap<String, EnumDef>, _> = json::decode(&enums.to_string());
+            for (name, def) in unwrap_or!(enums.ok(), return None) {
+                config.add_enum(name, def);
+            }
+        }
+
+        if let Some(unions) = info.find("union") {
+            let unions: Result<HashMap<String, UnionDef>, _> = json::decode(&unions.to_string());
+            for (name, def) in unwrap_or!(unions.ok(), return None) {
+                config.add_union(name, def);
+            }
+        }
+
+        if let Some(structs) = info.find("struct") {
+            let structs: Result<HashMap<String, StructDef>, _> = json::decode(&structs.to_string());
+            for (name, def) in unwrap_or!(structs.ok(), return None) {
+                config.add_struct(name, def);
+            }
+        }
+        if !config.is_valid_fields() {
+            return None;
+        }
+        Some(config)
     }
 
+    /// the legacy config of the global fields and the protos only, the enum, union
+    /// and struct sections need `new_from_full_str`
     pub fn new(field: &str, proto: &str) -> Option<Config> {
         let field: Result<HashMap<String, Field>, _> = json::decode(field);
         let proto: Result<HashMap<String, Proto>, _> = json::decode(proto);
         if field.is_err() || proto.is_err() {
             return None;
         }
-        Some(Self::new_from_map(field.ok().unwrap(), proto.ok().unwrap()))
+        let config = Self::new_from_map(field.ok().unwrap(), proto.ok().unwrap());
+        if !config.is_valid_fields() {
+            return None;
+        }
+        Some(config)
     }
 
-    pub fn get_field_by_name(&self, name: &String) -> Option<&Field> {
+    pub fn get_field_by_name(&self, name: &str) -> Option<&Field> {
         self.field.get(name)
     }
 
@@ -108,15 +293,187 @@ impl Config {
         self.field.get(name)
     }
 
-    pub fn get_proto_by_name(&self, name: &String) -> Option<&Proto> {
+    pub fn get_proto_by_name(&self, name: &str) -> Option<&Proto> {
         self.proto.get(name)
     }
 
+    pub fn get_fields(&self) -> &HashMap<String, Field> {
+        &self.field
+    }
+
     pub fn get_field_index_name(&self, index: &u16) -> Option<&String> {
         self.index_field.get(index)
     }
 
-    pub fn get_proto_msg_type(&self, name: &String) -> Option<&String> {
+    pub fn get_proto_msg_type(&self, name: &str) -> Option<&String> {
         self.msg_proto.get(name)
     }
+
+    /// encoders skip the fields which equal to their default, decoders fill them back
+    pub fn set_omit_default(&mut self, omit_default: bool) {
+        self.omit_default = omit_default;
+    }
+
+    pub fn is_omit_default(&self) -> bool {
+        self.omit_default
+    }
+
+    /// str and raw payloads of odd length get a zero byte so the next field starts on
+    /// a 2-byte boundary, the length prefix keeps the unpadded length
+    pub fn set_align(&mut self, align: bool) {
+        self.align = align;
+    }
+
+    pub fn is_align(&self) -> bool {
+        self.align
+    }
+
+    /// the padding bytes after a str or raw payload of the length
+    pub fn get_padding_len(&self, len: usize) -> usize {
+        if self.align { len % 2 } else { 0 }
+    }
+
+    /// the frame readers fail with `BufferOverMaxError` on a larger frame before
+    /// its payload is buffered, the stream readers on a larger message
+    pub fn set_max_frame_len(&mut self, max_frame_len: usize) {
+        self.max_frame_len = max_frame_len;
+    }
+
+    pub fn get_max_frame_len(&self) -> usize {
+        self.max_frame_len
+    }
+
+    /// `required` and `default` are only allowed on struct fields, an untyped map
+    /// carries any of the global fields, and every default must parse by its pattern,
+    /// the enum values must fit the unsigned base pattern and name one variant each,
+    /// the union tags one arm each and the struct indices one field each
+    fn is_valid_fields(&self) -> bool {
+        if self.field.values().any(|field| field.required.is_some() || field.has_default()) {
+            return false;
+        }
+        if !self.enums.values().all(|def| def.is_valid()) || !self.unions.values().all(|def| def.is_valid()) {
+            return false;
+        }
+        if !self.structs.values().all(|def| def.is_valid()) {
+            return false;
+        }
+        self.structs.values()
+            .flat_map(|def| def.fields.values())
+            .all(|field| !field.has_default() || self.get_default_value(field).is_some())
+    }
+
+    pub fn get_default_value(&self, field: &Field) -> Option<Value> {
+        let default = unwrap_or!(field.default.as_ref(), return None);
+        let pattern = self.get_default_pattern(field);
+        if let Some(def) = self.get_enum_by_name(&pattern) {
+            let value = unwrap_or!(def.get_variant_value(default), return None);
+            return Some(Value::Enum(value, default.clone()));
+        }
+        match get_type_by_name(&pattern) {
+            TYPE_U8 => default.parse::<u8>().ok().map(Value::from),
+            TYPE_I8 => default.parse::<i8>().ok().map(Value::from),
+            TYPE_U16 => default.parse::<u16>().ok().map(Value::from),
+            TYPE_I16 => default.parse::<i16>().ok().map(Value::from),
+            TYPE_U32 => default.parse::<u32>().ok().map(Value::from),
+            TYPE_I32 => default.parse::<i32>().ok().map(Value::from),
+            TYPE_F32 => default.parse::<f32>().ok().map(Value::from),
+            TYPE_F64 => default.parse::<f64>().ok().map(Value::from),
+            TYPE_STR => Some(Value::from(default.clone())),
+            TYPE_RAW => Some(Value::from(default.clone().into_bytes())),
+            _ => None,
+        }
+    }
+
+    /// the pattern the default is parsed by, a nullable field defaults to its inner pattern
+    pub fn get_default_pattern(&self, field: &Field) -> String {
+        self.get_nullable_inner(&field.pattern).unwrap_or_else(|| field.pattern.clone())
+    }
+
+    pub fn add_enum(&mut self, name: String, mut def: EnumDef) {
+        let value_name = def.variants.iter().map(|(name, value)| (*value, name.clone())).collect();
+        def.value_name = Some(value_name);
+        self.enums.insert(name, def);
+    }
+
+    pub fn get_enum_by_name(&self, name: &String) -> Option<&EnumDef> {
+        self.enums.get(name)
+    }
+
+    pub fn add_union(&mut self, name: String, mut def: UnionDef) {
+        let tag_arm = def.arms.iter().map(|(name, arm)| (arm.tag, name.clone())).collect();
+        def.tag_arm = Some(tag_arm);
+        self.unions.insert(name, def);
+    }
+
+    pub fn get_union_by_name(&self, name: &String) -> Option<&UnionDef> {
+        self.unions.get(name)
+    }
+
+    pub fn add_struct(&mut self, name: String, mut def: StructDef) {
+        let index_field = def.fields.iter().map(|(name, field)| (field.index, name.clone())).collect();
+        def.index_field = Some(index_field);
+        self.structs.insert(name, def);
+    }
+
+    pub fn get_struct_by_name(&self, name: &String) -> Option<&StructDef> {
+        self.structs.get(name)
+    }
+
+    /// the element struct of an array pattern such as `PlayerInfo[]`
+    pub fn get_struct_by_vec_pattern(&self, pattern: &str) -> Option<&StructDef> {
+        if !pattern.ends_with("[]") {
+            return None;
+        }
+        self.structs.get(&pattern[..pattern.len() - 2])
+    }
+
+    /// the inner pattern of a nullable pattern such as `u32?`
+    pub fn get_nullable_inner(&self, pattern: &str) -> Option<String> {
+        if !pattern.ends_with("?") {
+            return None;
+        }
+        Some(pattern[..pattern.len() - 1].to_string())
+    }
+
+    /// the list pattern of a list with nullable elements such as `u32?[]`, the other
+    /// lists reject null elements
+    pub fn get_nullable_elem_vec(&self, pattern: &str) -> Option<String> {
+        if !pattern.ends_with("?[]") {
+            return None;
+        }
+        Some(format!("{}[]", &pattern[..pattern.len() - 3]))
+    }
+
+    /// whether the pattern names an enum, union or struct declared in the config
+    pub fn is_schema_pattern(&self, pattern: &String) -> bool {
+        self.get_nullable_inner(pattern).is_some()
+            || self.get_nullable_elem_vec(pattern).is_some()
+            || self.get_enum_by_name(pattern).is_some()
+            || self.get_union_by_name(pattern).is_some()
+            || self.get_struct_by_name(pattern).is_some()
+            || self.get_struct_by_vec_pattern(pattern).is_some()
+    }
+
+    /// the type written on the wire for a field pattern, named enums resolve to their base type
+    pub fn get_pattern_type(&self, pattern: &String) -> u16 {
+        if let Some(inner) = self.get_nullable_inner(pattern) {
+            return self.get_pattern_type(&inner);
+        }
+        if let Some(inner) = self.get_nullable_elem_vec(pattern) {
+            return self.get_pattern_type(&inner);
+        }
+        if let Some(def) = self.get_enum_by_name(pattern) {
+            return get_type_by_name(&def.pattern);
+        }
+        if self.get_union_by_name(pattern).is_some() {
+            return TYPE_UNION;
+        }
+        if self.get_struct_by_name(pattern).is_some() {
+            return TYPE_MAP;
+        }
+        if self.get_struct_by_vec_pattern(pattern).is_some() {
+            return TYPE_VEC_MAP;
+        }
+        get_type_by_name(pattern)
+    }
 }
\ No newline at end of file
@@ -1,59 +1,60 @@
 use std::mem;
-use std::io::Read;
 use crate::error::ErrorKind;
 use crate::value::*;
 use crate::buffer::*;
+use crate::proto_io::{ProtoRead, Checkpoint};
 use crate::error::RpResult;
-use crate::config::{Config, Field};
+use crate::config::{Config, Field, EnumDef, UnionDef, StructDef};
 use crate::macro_use::*;
+use crate::decode_ref::{SliceReader, read_str_ref, is_terminator};
 use std::collections::HashMap;
 
-pub fn decode_number(buffer: &mut Buffer, value_type: u16) -> RpResult<Value> {
+pub fn decode_number<R: ProtoRead>(buffer: &mut R, value_type: u16) -> RpResult<Value> {
     match value_type {
         TYPE_U8 => {
             let data: &mut [u8; 1] = &mut [0];
-            try_read!(buffer.read(data), data.len());
+            buffer.read_fill(data)?;
             Ok(Value::from(data[0]))
         }
         TYPE_I8 => {
             let data: &mut [u8; 1] = &mut [0];
-            try_read!(buffer.read(data), data.len());
+            buffer.read_fill(data)?;
             Ok(Value::from(data[0] as i8))
         }
         TYPE_U16 => {
             let data: &mut [u8; 2] = &mut [0, 0];
-            try_read!(buffer.read(data), data.len());
-            let value = unsafe { mem::transmute::<[u8; 2], u16>(*data)};
+            buffer.read_fill(data)?;
+            let value = u16::from_le_bytes(*data);
             Ok(Value::from(value))
         }
         TYPE_I16 => {
             let data: &mut [u8; 2] = &mut [0, 0];
-            try_read!(buffer.read(data), data.len());
-            let value = unsafe {mem::transmute::<[u8; 2], i16>(*data)};
+            buffer.read_fill(data)?;
+            let value = i16::from_le_bytes(*data);
             Ok(Value::from(value))
         }
         TYPE_U32 => {
             let data: &mut [u8; 4] = &mut [0, 0, 0, 0];
-            try_read!(buffer.read(data), data.len());
-            let value = unsafe {mem::transmute::<[u8; 4], u32>(*data)};
+            buffer.read_fill(data)?;
+            let value = u32::from_le_bytes(*data);
             Ok(Value::from(value))
         }
         TYPE_I32 => {
             let data: &mut [u8; 4] = &mut [0, 0, 0, 0];
-            try_read!(buffer.read(data), data.len());
-            let value = unsafe {mem::transmute::<[u8; 4], i32>(*data)};
+            buffer.read_fill(data)?;
+            let value = i32::from_le_bytes(*data);
             Ok(Value::from(value))
         }
         TYPE_F32 => {
             let data: &mut [u8; 4] = &mut [0, 0, 0, 0];
-            try_read!(buffer.read(data), data.len());
-            let value = unsafe {mem::transmute::<[u8; 4], f32>(*data)};
+            buffer.read_fill(data)?;
+            let value = i32::from_le_bytes(*data) as f32 / 1000.0;
             Ok(Value::from(value))
         }
         TYPE_F64 => {
             let data: &mut [u8; 8] = &mut [0, 0, 0, 0, 0, 0, 0, 0];
-            try_read!(buffer.read(data), data.len());
-            let value = unsafe {mem::transmute::<[u8; 8], f64>(*data)};
+            buffer.read_fill(data)?;
+            let value = i64::from_le_bytes(*data) as f64 / 1000.0;
             Ok(Value::from(value))
         }
         _ => {
@@ -62,122 +63,563 @@ pub fn decode_number(buffer: &mut Buffer, value_type: u16) -> RpResult<Value> {
     }
 }
 
-pub fn decode_str_raw(buffer: &mut Buffer, value_type: u16) -> RpResult<Value> {
+/// the value the decoders build, `Value` copies everything out of the input and
+/// `ValueRef` borrows the strings and raw bytes from it
+pub trait DecodeValue<'a>: Sized {
+    fn from_number(value: Value) -> Self;
+    fn new_null() -> Self;
+    fn new_map(map: HashMap<&'a str, Self>) -> Self;
+    /// a list of the list type
+    fn new_vec(value_type: u16, value: Vec<Self>) -> Self;
+    fn new_enum(number: u32, name: &'a str) -> Self;
+    fn new_union(tag: u16, name: &'a str, value: Self) -> Self;
+    /// the type on the wire, `TYPE_NIL` for null
+    fn get_type(&self) -> u16;
+    /// the number of an enum base value
+    fn get_number(&self) -> Option<u32>;
+    fn get_default(config: &'a Config, field: &'a Field) -> Option<Self>;
+}
+
+/// a value type which can be decoded from the reader
+pub trait DecodeFrom<'a, R: ProtoRead>: DecodeValue<'a> {
+    /// the str or raw payload after the length prefix, the padding is skipped
+    fn decode_str_raw(reader: &mut R, config: &Config, value_type: u16) -> RpResult<Self>;
+}
+
+impl<'a> DecodeValue<'a> for Value {
+    fn from_number(value: Value) -> Value {
+        value
+    }
+
+    fn new_null() -> Value {
+        Value::Null
+    }
+
+    fn new_map(map: HashMap<&'a str, Value>) -> Value {
+        Value::Map(map.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+    }
+
+    fn new_vec(value_type: u16, value: Vec<Value>) -> Value {
+        match value_type {
+            TYPE_VEC_U8 => Value::VecU8(value),
+            TYPE_VEC_I8 => Value::VecI8(value),
+            TYPE_VEC_U16 => Value::VecU16(value),
+            TYPE_VEC_I16 => Value::VecI16(value),
+            TYPE_VEC_U32 => Value::VecU32(value),
+            TYPE_VEC_I32 => Value::VecI32(value),
+            TYPE_VEC_F32 => Value::VecF32(value),
+            TYPE_VEC_F64 => Value::VecF64(value),
+            TYPE_VEC_STR => Value::VecStr(value),
+            TYPE_VEC_RAW => Value::VecRaw(value),
+            TYPE_VEC_MAP => Value::VecMap(value),
+            _ => unreachable!("not other type"),
+        }
+    }
+
+    fn new_enum(number: u32, name: &'a str) -> Value {
+        Value::Enum(number, name.to_string())
+    }
+
+    fn new_union(tag: u16, name: &'a str, value: Value) -> Value {
+        Value::Union(tag, name.to_string(), Box::new(value))
+    }
+
+    fn get_type(&self) -> u16 {
+        get_value_type(self)
+    }
+
+    fn get_number(&self) -> Option<u32> {
+        match *self {
+            Value::U8(val) => Some(val as u32),
+            Value::U16(val) => Some(val as u32),
+            Value::U32(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    fn get_default(config: &'a Config, field: &'a Field) -> Option<Value> {
+        config.get_default_value(field)
+    }
+}
+
+impl<'a, R: ProtoRead> DecodeFrom<'a, R> for Value {
+    fn decode_str_raw(reader: &mut R, config: &Config, value_type: u16) -> RpResult<Value> {
+        let len: u16 = decode_number(reader, TYPE_U16)?.into();
+        let mut data = vec![0; len as usize];
+        reader.read_fill(&mut data[..])?;
+        skip_padding(reader, config, len as usize)?;
+        match value_type {
+            TYPE_STR => {
+                let val = unwrap_or!(String::from_utf8(data).ok(),
+                                     fail!((ErrorKind::StringFormatError, "string format error")));
+                Ok(Value::from(val))
+            }
+            TYPE_RAW => Ok(Value::from(data)),
+            _ => {
+                unreachable!("not other type");
+            }
+        }
+    }
+}
+
+/// the read position is restored if the decode fails
+fn with_checkpoint<R, T, F>(buffer: &mut R, decode: F) -> RpResult<T>
+    where R: ProtoRead, F: FnOnce(&mut R) -> RpResult<T>
+{
+    let mut checkpoint = Checkpoint::new(buffer);
+    let value = decode(&mut checkpoint)?;
+    checkpoint.commit();
+    Ok(value)
+}
+
+fn skip_padding<R: ProtoRead>(buffer: &mut R, config: &Config, len: usize) -> RpResult<()> {
+    buffer.skip_bytes(config.get_padding_len(len))
+}
+
+fn is_vec_type(value_type: u16) -> bool {
+    get_elem_type(value_type) != TYPE_NULL
+}
+
+/// the element type of a list type
+fn get_elem_type(value_type: u16) -> u16 {
     match value_type {
-        TYPE_STR => {
-            let len : u16 = decode_number(buffer, TYPE_U16)?.into();
-            if len == 0 {
-                return Ok(Value::from(String::new()));
+        TYPE_VEC_U8 => TYPE_U8,
+        TYPE_VEC_I8 => TYPE_I8,
+        TYPE_VEC_U16 => TYPE_U16,
+        TYPE_VEC_I16 => TYPE_I16,
+        TYPE_VEC_U32 => TYPE_U32,
+        TYPE_VEC_I32 => TYPE_I32,
+        TYPE_VEC_F32 => TYPE_F32,
+        TYPE_VEC_F64 => TYPE_F64,
+        TYPE_VEC_STR => TYPE_STR,
+        TYPE_VEC_RAW => TYPE_RAW,
+        TYPE_VEC_MAP => TYPE_MAP,
+        _ => TYPE_NULL,
+    }
+}
+
+/// the index and the type of a field header
+pub fn read_header<R: ProtoRead>(buffer: &mut R) -> RpResult<(u16, u16)> {
+    with_checkpoint(buffer, |buffer| {
+        let index = decode_number(buffer, TYPE_U16)?.into();
+        let value_type = decode_number(buffer, TYPE_U16)?.into();
+        Ok((index, value_type))
+    })
+}
+
+pub fn decode_str_raw_as<'a, R, V>(buffer: &mut R, config: &'a Config, value_type: u16) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| V::decode_str_raw(buffer, config, value_type))
+}
+
+pub fn decode_map_as<'a, R, V>(buffer: &mut R, config: &'a Config) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| {
+        let mut map = HashMap::new();
+        loop {
+            let header = read_header(buffer)?;
+            if is_terminator(header) {
+                break;
             }
 
-            let mut data_vec = vec![0; len as usize];
-            try_read!(buffer.read(&mut data_vec[..]), len as usize);
-            let val = String::from_utf8(data_vec);
-            if val.is_err() {
-                fail!((ErrorKind::StringFormatError, "string format error"));
+            let name = match config.get_field_index_name(&header.0) {
+                Some(name) => name,
+                None => {
+                    decode_field_as::<R, V>(buffer, config)?;
+                    continue;
+                }
+            };
+            let pattern = &config.get_field_by_name(name).unwrap().pattern;
+            let sub_value = decode_field_by_pattern_as(buffer, config, pattern)?;
+            map.insert(&name[..], sub_value);
+        }
+        Ok(V::new_map(map))
+    })
+}
+
+/// check the required fields and fill the defaults of the fields the struct doesn't contain
+fn fill_default_values_as<'a, V>(config: &'a Config,
+                                 fields: &'a HashMap<String, Field>,
+                                 map: &mut HashMap<&'a str, V>)
+                                 -> RpResult<()>
+    where V: DecodeValue<'a>
+{
+    let mut missing: Vec<String> = vec![];
+    for (name, field) in fields {
+        if map.contains_key(&name[..]) {
+            continue;
+        }
+        match V::get_default(config, field) {
+            Some(value) => {
+                map.insert(&name[..], value);
             }
+            None if field.is_required() => missing.push(name.clone()),
+            None => {}
+        }
+    }
+    missing.sort();
+    ensure!(missing.is_empty(),
+            (ErrorKind::MissingError, "missing required fields", missing.join(", ")));
+    Ok(())
+}
+
+pub fn decode_enum_as<'a, V: DecodeValue<'a>>(def: &'a EnumDef, value: V) -> RpResult<V> {
+    let number = unwrap_or!(value.get_number(), fail!((ErrorKind::TypeNotMatchError, "must match type")));
+    let name = unwrap_or!(def.get_variant_name(number),
+                          fail!((ErrorKind::ParseError, "unknown enum variant", number.to_string())));
+    Ok(V::new_enum(number, name))
+}
+
+pub fn decode_field_as<'a, R, V>(buffer: &mut R, config: &'a Config) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    Ok(decode_element_as(buffer, config)?.unwrap_or_else(V::new_null))
+}
 
-            Ok(Value::from(val.ok().unwrap()))
+pub fn decode_struct_as<'a, R, V>(buffer: &mut R, config: &'a Config, def: &'a StructDef) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| {
+        let mut map = HashMap::new();
+        loop {
+            let header = read_header(buffer)?;
+            if is_terminator(header) {
+                break;
+            }
+
+            let (name, field) = match def.get_field_by_index(&header.0) {
+                Some(val) => val,
+                None => {
+                    decode_field_as::<R, V>(buffer, config)?;
+                    continue;
+                }
+            };
+            ensure!(header.1 == config.get_pattern_type(&field.pattern),
+                    (ErrorKind::TypeNotMatchError, "struct field not match the pattern", name.clone()));
+            let sub_value = decode_field_by_pattern_as(buffer, config, &field.pattern)?;
+            map.insert(&name[..], sub_value);
         }
-        TYPE_RAW => {
-            let len: u16 = (decode_number(buffer, TYPE_U16))?.into();
-            if len == 0 {
-                return Ok(Value::from(vec![]));
+
+        fill_default_values_as(config, &def.fields, &mut map)?;
+        Ok(V::new_map(map))
+    })
+}
+
+pub fn decode_struct_vec_as<'a, R, V>(buffer: &mut R, config: &'a Config, def: &'a StructDef) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    decode_struct_list_as(buffer, config, def, false)
+}
+
+fn decode_struct_list_as<'a, R, V>(buffer: &mut R, config: &'a Config, def: &'a StructDef, allow_null: bool) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| {
+        let mut value = vec![];
+        loop {
+            let header = read_header(buffer)?;
+            if is_terminator(header) {
+                break;
             }
-            let mut data_vec = vec![0; len as usize];
-            try_read!(buffer.read(&mut data_vec[..]), len as usize);
-            Ok(Value::from(data_vec))
+            if allow_null && header.1 == TYPE_NIL {
+                value.push(V::new_null());
+                continue;
+            }
+            ensure!(header.1 == TYPE_MAP, (ErrorKind::TypeNotMatchError, "must match type"));
+            value.push(decode_struct_as(buffer, config, def)?);
         }
-        _=> {
-            unreachable!("not other type");
+        Ok(V::new_vec(TYPE_VEC_MAP, value))
+    })
+}
+
+pub fn decode_union_as<'a, R, V>(buffer: &mut R, config: &'a Config, def: &'a UnionDef) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| {
+        let tag: u16 = decode_number(buffer, TYPE_U16)?.into();
+        let (name, arm) = unwrap_or!(def.get_arm_by_tag(tag),
+                                     fail!((ErrorKind::ParseError, "unknown union tag", tag.to_string())));
+        let header = read_header(buffer)?;
+        let is_null = header.1 == TYPE_NIL && config.get_nullable_inner(&arm.pattern).is_some();
+        ensure!(is_null || header.1 == config.get_pattern_type(&arm.pattern),
+                (ErrorKind::TypeNotMatchError, "union payload not match the arm", name.clone()));
+        let sub_value = decode_by_pattern_as(buffer, config, header.1, &arm.pattern)?;
+        Ok(V::new_union(tag, name, sub_value))
+    })
+}
+
+pub fn decode_field_by_pattern_as<'a, R, V>(buffer: &mut R, config: &'a Config, pattern: &String) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    Ok(decode_element_by_pattern_as(buffer, config, pattern)?.unwrap_or_else(V::new_null))
+}
+
+/// decode the next element of a list, None when reach the terminator
+pub fn decode_element_as<'a, R, V>(buffer: &mut R, config: &'a Config) -> RpResult<Option<V>>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| {
+        let header = read_header(buffer)?;
+        if is_terminator(header) {
+            return Ok(None);
         }
-    }
+        decode_by_type_as(buffer, config, header.1).map(Some)
+    })
 }
 
-pub fn decode_map(buffer: &mut Buffer, config: &Config) -> RpResult<Value> {
-    let mut map = HashMap::<String, Value>::new();
-    loop {
-        let field = read_field(buffer)?;
-        if field.is_null_type() {
-            return Ok(Value::from(map));
+pub fn decode_element_by_pattern_as<'a, R, V>(buffer: &mut R,
+                                              config: &'a Config,
+                                              pattern: &String)
+                                              -> RpResult<Option<V>>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    with_checkpoint(buffer, |buffer| {
+        let header = read_header(buffer)?;
+        if is_terminator(header) {
+            return Ok(None);
         }
+        decode_by_pattern_as(buffer, config, header.1, pattern).map(Some)
+    })
+}
 
-        let sub_value = decode_field(buffer, config)?;
-        let name = config.get_field_index_name(&field.index);
-        if name.is_none() {
-            continue;
+fn decode_by_pattern_as<'a, R, V>(buffer: &mut R, config: &'a Config, value_type: u16, pattern: &String) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    if let Some(inner) = config.get_nullable_inner(pattern) {
+        if value_type == TYPE_NIL {
+            return Ok(V::new_null());
         }
+        return decode_by_pattern_as(buffer, config, value_type, &inner);
+    }
+    if let Some(inner) = config.get_nullable_elem_vec(pattern) {
+        ensure!(value_type == config.get_pattern_type(&inner), (ErrorKind::TypeNotMatchError, "must match type"));
+        return match config.get_struct_by_vec_pattern(&inner) {
+            Some(def) => decode_struct_list_as(buffer, config, def, true),
+            None => decode_vec_as(buffer, config, value_type, true),
+        };
+    }
+    ensure!(value_type != TYPE_NIL, (ErrorKind::TypeNotMatchError, "null value for a non nullable pattern"));
+    if let Some(def) = config.get_union_by_name(pattern) {
+        ensure!(value_type == TYPE_UNION, (ErrorKind::TypeNotMatchError, "must match type"));
+        return decode_union_as(buffer, config, def);
+    }
+    if let Some(def) = config.get_struct_by_name(pattern) {
+        ensure!(value_type == TYPE_MAP, (ErrorKind::TypeNotMatchError, "must match type"));
+        return decode_struct_as(buffer, config, def);
+    }
+    if let Some(def) = config.get_struct_by_vec_pattern(pattern) {
+        ensure!(value_type == TYPE_VEC_MAP, (ErrorKind::TypeNotMatchError, "must match type"));
+        return decode_struct_vec_as(buffer, config, def);
+    }
+    if is_vec_type(get_type_by_name(pattern)) {
+        ensure!(value_type == get_type_by_name(pattern), (ErrorKind::TypeNotMatchError, "must match type"));
+        return decode_vec_as(buffer, config, value_type, false);
+    }
+    let value = decode_by_type_as(buffer, config, value_type)?;
+    match config.get_enum_by_name(pattern) {
+        Some(def) => decode_enum_as(def, value),
+        None => Ok(value),
+    }
+}
 
-        let name = name.map(|s| s.clone()).unwrap();
-        map.insert(name, sub_value);
+/// the untyped lists keep their null elements, a list pattern rejects them unless
+/// it is a `X?[]` pattern
+fn decode_vec_as<'a, R, V>(buffer: &mut R, config: &'a Config, value_type: u16, allow_null: bool) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    let elem_type = get_elem_type(value_type);
+    let mut value = vec![];
+    while let Some(sub_value) = decode_element_as::<R, V>(buffer, config)? {
+        let sub_type = sub_value.get_type();
+        ensure!((allow_null && sub_type == TYPE_NIL) || sub_type == elem_type,
+                (ErrorKind::TypeNotMatchError, "must match type"));
+        value.push(sub_value);
     }
+    Ok(V::new_vec(value_type, value))
 }
 
-pub fn read_field(buffer: &mut Buffer) -> RpResult<Field> {
-    let index = decode_number(buffer, TYPE_U16)?.into();
+fn decode_by_type_as<'a, R, V>(buffer: &mut R, config: &'a Config, value_type: u16) -> RpResult<V>
+    where R: ProtoRead, V: DecodeFrom<'a, R>
+{
+    match value_type {
+        TYPE_U8 | TYPE_I8 | TYPE_U16 | TYPE_I16 | TYPE_U32 | TYPE_I32 | TYPE_F32 | TYPE_F64 => {
+            Ok(V::from_number(decode_number(buffer, value_type)?))
+        }
+        TYPE_STR | TYPE_RAW => decode_str_raw_as(buffer, config, value_type),
+        TYPE_MAP => decode_map_as(buffer, config),
+        TYPE_VEC_U8 | TYPE_VEC_I8 | TYPE_VEC_U16 | TYPE_VEC_I16 | TYPE_VEC_U32 | TYPE_VEC_I32 |
+        TYPE_VEC_F32 | TYPE_VEC_F64 | TYPE_VEC_STR | TYPE_VEC_RAW | TYPE_VEC_MAP => {
+            decode_vec_as(buffer, config, value_type, true)
+        }
+        TYPE_NULL | TYPE_NIL => Ok(V::new_null()),
+        TYPE_UNION => fail!((ErrorKind::TypeNotMatchError, "union must decode by the union pattern")),
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    }
+}
 
-    let pattern = decode_number(buffer, TYPE_U16)?.into();
+pub fn decode_str_raw<R: ProtoRead>(buffer: &mut R, config: &Config, value_type: u16) -> RpResult<Value> {
+    decode_str_raw_as(buffer, config, value_type)
+}
+
+pub fn decode_map<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<Value> {
+    decode_map_as(buffer, config)
+}
+
+pub fn decode_enum(def: &EnumDef, value: Value) -> RpResult<Value> {
+    decode_enum_as(def, value)
+}
+
+pub fn read_field<R: ProtoRead>(buffer: &mut R) -> RpResult<Field> {
+    let (index, value_type) = read_header(buffer)?;
     Ok(Field{
         index,
-        pattern: get_name_by_type(pattern).to_string(),
+        pattern: get_name_by_type(value_type).to_string(),
+        required: None,
+        default: None,
     })
 }
 
-pub fn decode_field(buffer: &mut Buffer, config: &Config) -> RpResult<Value> {
-    let field = read_field(buffer)?;
-    if field.is_null_type() {
-        return Ok(Value::Null);
+pub fn decode_field<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<Value> {
+    decode_field_as(buffer, config)
+}
+
+pub fn decode_struct<R: ProtoRead>(buffer: &mut R, config: &Config, def: &StructDef) -> RpResult<Value> {
+    decode_struct_as(buffer, config, def)
+}
+
+pub fn decode_struct_vec<R: ProtoRead>(buffer: &mut R, config: &Config, def: &StructDef) -> RpResult<Value> {
+    decode_struct_vec_as(buffer, config, def)
+}
+
+pub fn decode_union<R: ProtoRead>(buffer: &mut R, config: &Config, def: &UnionDef) -> RpResult<Value> {
+    decode_union_as(buffer, config, def)
+}
+
+pub fn decode_field_by_pattern<R: ProtoRead>(buffer: &mut R, config: &Config, pattern: &String) -> RpResult<Value> {
+    decode_field_by_pattern_as(buffer, config, pattern)
+}
+
+/// decode the next element of a list, None when reach the terminator
+pub fn decode_element<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<Option<Value>> {
+    decode_element_as(buffer, config)
+}
+
+pub fn decode_element_by_pattern<R: ProtoRead>(buffer: &mut R,
+                                 config: &Config,
+                                 pattern: &String)
+                                 -> RpResult<Option<Value>> {
+    decode_element_by_pattern_as(buffer, config, pattern)
+}
+
+/// the name of the message at the read position, the buffer is not consumed
+pub fn peek_proto_name(buffer: &Buffer) -> RpResult<&str> {
+    let mut reader = SliceReader::new(&buffer.get_data()[buffer.get_read_pos()..]);
+    match read_str_ref(&mut reader) {
+        Ok(name) => Ok(name),
+        Err(ref err) if err.kind() == ErrorKind::NoLeftSpaceError => {
+            fail!((ErrorKind::IncompleteError, "the message is incomplete"))
+        }
+        Err(err) => Err(err),
     }
-    decode_by_field(buffer, config, &field)
 }
 
-fn decode_by_field(buffer: &mut Buffer, config: & Config, field: &Field) -> RpResult<Value> {
-    let t = get_type_by_name(&*field.pattern);
-    match t {
-        TYPE_U8 | TYPE_I8 | TYPE_U16 | TYPE_I16 | TYPE_U32 | TYPE_I32 | TYPE_F32 | TYPE_F64 => {
-            decode_number(buffer, t)
-        }
-        TYPE_STR | TYPE_RAW => decode_str_raw(buffer, t),
-        TYPE_MAP => decode_map(buffer, config),
-        TYPE_VEC_U8 => decode_array!(decode_field(buffer, config), Value::VecU8, Value::U8),
-        TYPE_VEC_I8 => decode_array!(decode_field(buffer, config), Value::VecI8, Value::I8),
-        TYPE_VEC_U16 => decode_array!(decode_field(buffer, config), Value::VecU16, Value::U16),
-        TYPE_VEC_I16 => decode_array!(decode_field(buffer, config), Value::VecI16, Value::I16),
-        TYPE_VEC_U32 => decode_array!(decode_field(buffer, config), Value::VecU32, Value::U32),
-        TYPE_VEC_I32 => decode_array!(decode_field(buffer, config), Value::VecI32, Value::I32),
-        TYPE_VEC_F32 => decode_array!(decode_field(buffer, config), Value::VecF32, Value::F32),
-        TYPE_VEC_F64 => decode_array!(decode_field(buffer, config), Value::VecF64, Value::F64),
-        TYPE_VEC_STR => decode_array!(decode_field(buffer, config), Value::VecStr, Value::Str),
-        TYPE_VEC_RAW => decode_array!(decode_field(buffer, config), Value::VecRaw, Value::Raw),
-        TYPE_VEC_MAP => decode_array!(decode_field(buffer, config), Value::VecMap, Value::Map),
-        TYPE_NULL => Ok(Value::Null),
-        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+/// the msg_type of the message at the read position, the buffer is not consumed
+pub fn peek_msg_type<'a>(buffer: &Buffer, config: &'a Config) -> RpResult<Option<&'a String>> {
+    let name = peek_proto_name(buffer)?;
+    Ok(config.get_proto_msg_type(name))
+}
+
+/// decode a whole message, the read position is restored if it fails and
+/// `IncompleteError` returned if the buffer doesn't hold the whole message yet
+pub fn decode_proto<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<(String, Vec<Value>)> {
+    let mut checkpoint = Checkpoint::new(buffer);
+    let mut decoder = StreamDecoder::new();
+    match decoder.decode(&mut *checkpoint, config)? {
+        Some(result) => {
+            checkpoint.commit();
+            Ok(result)
+        }
+        None => fail!((ErrorKind::IncompleteError, "the message is incomplete")),
     }
 }
 
-pub fn decode_proto(buffer: &mut Buffer, config: &Config) -> RpResult<(String, Vec<Value>)> {
-    let name = decode_str_raw(buffer, TYPE_STR).unwrap().into();
+/// decode messages from a buffer which is filled by partial reads, the name and
+/// the finished top level args are kept between calls so they are not parsed again,
+/// an unfinished arg is parsed again from its start on every call, so a message of
+/// one large arg such as a big `VecMap` costs quadratic time and is better decoded
+/// once it is whole, e.g. after `check_frame` reports a ready frame, the message
+/// length is not bounded here, `IoReader::set_max_len` bounds it on a stream
+pub struct StreamDecoder {
+    name: Option<String>,
+    args: Vec<Value>,
+}
 
-    let mut value: Vec<Value> = vec![];
-    loop {
-        let sub_value = decode_field(buffer, config)?;
-        match sub_value {
-            Value::Null => break,
-            _ => (),
+impl StreamDecoder {
+    pub fn new() -> StreamDecoder {
+        StreamDecoder {
+            name: None,
+            args: vec![],
         }
-        value.push(sub_value);
     }
 
-    let proto = config.get_proto_by_name(&name);
-    match proto {
-        Some(val) => {
-            if val.args.len() != value.len() {
+    /// returns None if more bytes are needed, the unfinished part is not consumed
+    pub fn decode<R: ProtoRead>(&mut self, buffer: &mut R, config: &Config) -> RpResult<Option<(String, Vec<Value>)>> {
+        if self.name.is_none() {
+            match decode_str_raw(buffer, config, TYPE_STR) {
+                Ok(name) => self.name = Some(name.into()),
+                Err(ref err) if err.kind() == ErrorKind::NoLeftSpaceError => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+
+        let proto = match config.get_proto_by_name(self.name.as_ref().unwrap()) {
+            Some(proto) => proto,
+            None => {
+                self.reset();
                 fail!((ErrorKind::TypeNotMatchError, "must match type"));
             }
+        };
+
+        loop {
+            let sub_value = match proto.args.get(self.args.len()) {
+                Some(pattern) => decode_element_by_pattern(buffer, config, pattern),
+                None => decode_element(buffer, config),
+            };
+            match sub_value {
+                Ok(Some(sub_value)) => self.args.push(sub_value),
+                Ok(None) => break,
+                Err(ref err) if err.kind() == ErrorKind::NoLeftSpaceError => return Ok(None),
+                Err(err) => {
+                    self.reset();
+                    return Err(err);
+                }
+            }
         }
-        _ => {
+
+        let name = self.name.take().unwrap();
+        let value = mem::take(&mut self.args);
+        if proto.args.len() != value.len() {
             fail!((ErrorKind::TypeNotMatchError, "must match type"));
         }
+        Ok(Some((name, value)))
     }
-    Ok((name, value))
-}
\ No newline at end of file
+
+    /// drop the unfinished message
+    pub fn reset(&mut self) {
+        self.name = None;
+        self.args.clear();
+    }
+
+    /// whether part of a message has been decoded
+    pub fn is_pending(&self) -> bool {
+        self.name.is_some()
+    }
+}
+
+impl Default for StreamDecoder {
+    fn default() -> StreamDecoder {
+        StreamDecoder::new()
+    }
+}
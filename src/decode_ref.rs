@@ -0,0 +1,271 @@
+use std::str;
+use std::collections::HashMap;
+use crate::error::{ErrorKind, RpResult};
+use crate::value::*;
+use crate::value_ref::ValueRef;
+use crate::config::{Config, Field, EnumDef, UnionDef, StructDef};
+use crate::decode::*;
+
+/// reads the encoded bytes in place, the decoded strings and raw bytes borrow from it
+pub struct SliceReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> SliceReader<'a> {
+    pub fn new(data: &'a [u8]) -> SliceReader<'a> {
+        SliceReader {
+            data,
+            pos: 0,
+        }
+    }
+
+    pub fn get_pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    /// the bytes from `start` to the current position
+    pub fn read_span(&self, start: usize) -> &'a [u8] {
+        &self.data[start..self.pos]
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> RpResult<&'a [u8]> {
+        ensure!(self.remaining() >= len,
+                (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        let data = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(data)
+    }
+}
+
+/// decode a whole message from the bytes without copying the strings and raw bytes
+pub fn decode_proto_ref<'a>(data: &'a [u8], config: &'a Config) -> RpResult<(&'a str, Vec<ValueRef<'a>>)> {
+    decode_proto_from(&mut SliceReader::new(data), config)
+}
+
+/// decode the next message of the reader, the position is kept on error
+pub fn decode_proto_from<'a>(reader: &mut SliceReader<'a>,
+                             config: &'a Config)
+                             -> RpResult<(&'a str, Vec<ValueRef<'a>>)> {
+    let pos = reader.get_pos();
+    let result = decode_proto_inner(reader, config);
+    match result {
+        Err(ref err) if err.kind() == ErrorKind::NoLeftSpaceError => {
+            reader.set_pos(pos);
+            fail!((ErrorKind::IncompleteError, "the message is incomplete"))
+        }
+        Err(err) => {
+            reader.set_pos(pos);
+            Err(err)
+        }
+        Ok(result) => Ok(result),
+    }
+}
+
+fn decode_proto_inner<'a>(reader: &mut SliceReader<'a>,
+                          config: &'a Config)
+                          -> RpResult<(&'a str, Vec<ValueRef<'a>>)> {
+    let name = match decode_str_raw_ref(reader, config, TYPE_STR)? {
+        ValueRef::Str(name) => name,
+        _ => unreachable!("decode str only"),
+    };
+    let proto = unwrap_or!(config.get_proto_by_name(name),
+                           fail!((ErrorKind::TypeNotMatchError, "must match type")));
+
+    let mut value = vec![];
+    loop {
+        let sub_value = match proto.args.get(value.len()) {
+            Some(pattern) => decode_element_by_pattern_ref(reader, config, pattern)?,
+            None => decode_element_ref(reader, config)?,
+        };
+        value.push(unwrap_or!(sub_value, break));
+    }
+
+    if proto.args.len() != value.len() {
+        fail!((ErrorKind::TypeNotMatchError, "must match type"));
+    }
+    Ok((name, value))
+}
+
+/// the index and the type of a field header
+pub fn read_field_ref(reader: &mut SliceReader) -> RpResult<(u16, u16)> {
+    read_header(reader)
+}
+
+pub fn read_u16(reader: &mut SliceReader) -> RpResult<u16> {
+    Ok(decode_number(reader, TYPE_U16)?.into())
+}
+
+/// whether the field header ends a list or a map
+pub fn is_terminator(header: (u16, u16)) -> bool {
+    header.0 == 0 && header.1 == TYPE_NULL
+}
+
+/// the same little endian layout as `decode_number`
+pub fn decode_number_ref<'a>(reader: &mut SliceReader<'a>, value_type: u16) -> RpResult<ValueRef<'a>> {
+    Ok(ValueRef::from_number(decode_number(reader, value_type)?))
+}
+
+fn to_str(data: &[u8]) -> RpResult<&str> {
+    let val = unwrap_or!(str::from_utf8(data).ok(),
+                         fail!((ErrorKind::StringFormatError, "string format error")));
+    Ok(val)
+}
+
+/// the str payload without the padding, enough to peek the proto name
+pub fn read_str_ref<'a>(reader: &mut SliceReader<'a>) -> RpResult<&'a str> {
+    let len = read_u16(reader)? as usize;
+    to_str(reader.read_bytes(len)?)
+}
+
+impl<'a> DecodeValue<'a> for ValueRef<'a> {
+    fn from_number(value: Value) -> ValueRef<'a> {
+        match value {
+            Value::U8(val) => ValueRef::U8(val),
+            Value::I8(val) => ValueRef::I8(val),
+            Value::U16(val) => ValueRef::U16(val),
+            Value::I16(val) => ValueRef::I16(val),
+            Value::U32(val) => ValueRef::U32(val),
+            Value::I32(val) => ValueRef::I32(val),
+            Value::F32(val) => ValueRef::F32(val),
+            Value::F64(val) => ValueRef::F64(val),
+            _ => unreachable!("numbers only"),
+        }
+    }
+
+    fn new_null() -> ValueRef<'a> {
+        ValueRef::Null
+    }
+
+    fn new_map(map: HashMap<&'a str, ValueRef<'a>>) -> ValueRef<'a> {
+        ValueRef::Map(map)
+    }
+
+    fn new_vec(value_type: u16, value: Vec<ValueRef<'a>>) -> ValueRef<'a> {
+        match value_type {
+            TYPE_VEC_U8 => ValueRef::VecU8(value),
+            TYPE_VEC_I8 => ValueRef::VecI8(value),
+            TYPE_VEC_U16 => ValueRef::VecU16(value),
+            TYPE_VEC_I16 => ValueRef::VecI16(value),
+            TYPE_VEC_U32 => ValueRef::VecU32(value),
+            TYPE_VEC_I32 => ValueRef::VecI32(value),
+            TYPE_VEC_F32 => ValueRef::VecF32(value),
+            TYPE_VEC_F64 => ValueRef::VecF64(value),
+            TYPE_VEC_STR => ValueRef::VecStr(value),
+            TYPE_VEC_RAW => ValueRef::VecRaw(value),
+            TYPE_VEC_MAP => ValueRef::VecMap(value),
+            _ => unreachable!("not other type"),
+        }
+    }
+
+    fn new_enum(number: u32, name: &'a str) -> ValueRef<'a> {
+        ValueRef::Enum(number, name)
+    }
+
+    fn new_union(tag: u16, name: &'a str, value: ValueRef<'a>) -> ValueRef<'a> {
+        ValueRef::Union(tag, name, Box::new(value))
+    }
+
+    fn get_type(&self) -> u16 {
+        ValueRef::get_type(self)
+    }
+
+    fn get_number(&self) -> Option<u32> {
+        match *self {
+            ValueRef::U8(val) => Some(val as u32),
+            ValueRef::U16(val) => Some(val as u32),
+            ValueRef::U32(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// the strings borrow the default from the config
+    fn get_default(config: &'a Config, field: &'a Field) -> Option<ValueRef<'a>> {
+        let default = unwrap_or!(field.default.as_ref(), return None);
+        let pattern = config.get_default_pattern(field);
+        if let Some(def) = config.get_enum_by_name(&pattern) {
+            let (name, value) = unwrap_or!(def.variants.get_key_value(default), return None);
+            return Some(ValueRef::Enum(*value, name));
+        }
+        match get_type_by_name(&pattern) {
+            TYPE_STR => Some(ValueRef::Str(default)),
+            TYPE_RAW => Some(ValueRef::Raw(default.as_bytes())),
+            _ => config.get_default_value(field).map(ValueRef::from_number),
+        }
+    }
+}
+
+impl<'a> DecodeFrom<'a, SliceReader<'a>> for ValueRef<'a> {
+    fn decode_str_raw(reader: &mut SliceReader<'a>, config: &Config, value_type: u16) -> RpResult<ValueRef<'a>> {
+        let len = read_u16(reader)? as usize;
+        let data = reader.read_bytes(len)?;
+        reader.read_bytes(config.get_padding_len(len))?;
+        match value_type {
+            TYPE_STR => Ok(ValueRef::Str(to_str(data)?)),
+            TYPE_RAW => Ok(ValueRef::Raw(data)),
+            _ => {
+                unreachable!("not other type");
+            }
+        }
+    }
+}
+
+pub fn decode_str_raw_ref<'a>(reader: &mut SliceReader<'a>,
+                              config: &'a Config,
+                              value_type: u16)
+                              -> RpResult<ValueRef<'a>> {
+    decode_str_raw_as(reader, config, value_type)
+}
+
+pub fn decode_map_ref<'a>(reader: &mut SliceReader<'a>, config: &'a Config) -> RpResult<ValueRef<'a>> {
+    decode_map_as(reader, config)
+}
+
+pub fn decode_field_ref<'a>(reader: &mut SliceReader<'a>, config: &'a Config) -> RpResult<ValueRef<'a>> {
+    decode_field_as(reader, config)
+}
+
+pub fn decode_field_by_pattern_ref<'a>(reader: &mut SliceReader<'a>,
+                                       config: &'a Config,
+                                       pattern: &String)
+                                       -> RpResult<ValueRef<'a>> {
+    decode_field_by_pattern_as(reader, config, pattern)
+}
+
+/// decode the next element of a list, None when reach the terminator
+pub fn decode_element_ref<'a>(reader: &mut SliceReader<'a>, config: &'a Config) -> RpResult<Option<ValueRef<'a>>> {
+    decode_element_as(reader, config)
+}
+
+pub fn decode_element_by_pattern_ref<'a>(reader: &mut SliceReader<'a>,
+                                         config: &'a Config,
+                                         pattern: &String)
+                                         -> RpResult<Option<ValueRef<'a>>> {
+    decode_element_by_pattern_as(reader, config, pattern)
+}
+
+pub fn decode_enum_ref<'a>(def: &'a EnumDef, value: ValueRef<'a>) -> RpResult<ValueRef<'a>> {
+    decode_enum_as(def, value)
+}
+
+pub fn decode_union_ref<'a>(reader: &mut SliceReader<'a>,
+                            config: &'a Config,
+                            def: &'a UnionDef)
+                            -> RpResult<ValueRef<'a>> {
+    decode_union_as(reader, config, def)
+}
+
+pub fn decode_struct_ref<'a>(reader: &mut SliceReader<'a>,
+                             config: &'a Config,
+                             def: &'a StructDef)
+                             -> RpResult<ValueRef<'a>> {
+    decode_struct_as(reader, config, def)
+}
@@ -1,80 +1,163 @@
-use std::io::Write;
-use std::mem;
 use crate::value::*;
-use crate::{Buffer};
+use crate::proto_io::{ProtoWrite, WriteCheckpoint};
 use crate::error::*;
-use crate::config::{Config, Field};
+use crate::config::{Config, Field, EnumDef, UnionDef, StructDef};
 
-fn write_str_field(buffer: &mut Buffer, pattern: &str) ->RpResult<bool> {
+fn write_str_field<W: ProtoWrite>(buffer: &mut W, pattern: &str) ->RpResult<bool> {
     encode_number(buffer, &Value::from(0 as u16))?;
     encode_number(buffer, &Value::U16(get_type_by_name(pattern)))?;
     Ok(true)
 }
 
-fn append_and_align(buffer: &mut Buffer, val: &[u8]) -> RpResult<()> {
-    let _add = match val.len() % 2 {
-        0 => 0,
-        val => 2 - val,
-    };
-    buffer.write(val)?;
+fn append_and_align<W: ProtoWrite>(buffer: &mut W, config: &Config, val: &[u8]) -> RpResult<()> {
+    buffer.write_bytes(val)?;
+    if config.get_padding_len(val.len()) > 0 {
+        buffer.write_bytes(&[0])?;
+    }
     Ok(())
 }
 
-pub fn encode_number(buffer: &mut Buffer, value: &Value) -> RpResult<()> {
+pub fn encode_number<W: ProtoWrite>(buffer: &mut W, value: &Value) -> RpResult<()> {
     match *value {
         Value::U8(val) => {
-            buffer.write(unsafe { &mem::transmute::<u8, [u8; 1]>(val) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::I8(val) => {
-            buffer.write(unsafe { &mem::transmute::<i8, [u8; 1]>(val) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::U16(val) => {
-            buffer.write(unsafe { &mem::transmute::<u16, [u8; 2]>(val.to_le()) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::I16(val) => {
-            buffer.write(unsafe { &mem::transmute::<i16, [u8; 2]>(val.to_le()) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::U32(val) => {
-            buffer.write(unsafe { &mem::transmute::<u32, [u8; 4]>(val.to_le()) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::I32(val) => {
-            buffer.write(unsafe { &mem::transmute::<i32, [u8; 4]>(val.to_le()) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::F32(val) => {
             let val = (val * 1000.0) as i32;
-            buffer.write(unsafe { &mem::transmute::<i32, [u8; 4]>(val.to_le()) })?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         Value::F64(val) => {
             let val = (val * 1000.0) as i64;
-            buffer.write(unsafe {&mem::transmute::<i64, [u8; 8]>(val.to_le())})?;
+            buffer.write_bytes(&val.to_le_bytes())?;
         }
         _ => unreachable!("encode_number only"),
     }
     Ok(())
 }
 
-pub fn encode_str_raw(buffer: &mut Buffer, value: &Value) -> RpResult<()> {
+pub fn encode_str_raw<W: ProtoWrite>(buffer: &mut W, config: &Config, value: &Value) -> RpResult<()> {
     match *value {
         Value::Str(ref val) => {
             encode_number(buffer, &Value::U16(val.len() as u16))?;
-            append_and_align(buffer, &val.as_bytes()[..])?;
+            append_and_align(buffer, config, &val.as_bytes()[..])?;
         }
         Value::Raw(ref val) => {
             encode_number(buffer, &Value::U16(val.len() as u16))?;
-            append_and_align(buffer, &val[..])?;
+            append_and_align(buffer, config, &val[..])?;
         }
         _ => unreachable!("encode_str_raw only"),
     }
     Ok(())
 }
 
-pub fn encode_map(buffer: &mut Buffer, config: &Config, value: &Value) -> RpResult<()> {
+pub fn encode_enum<W: ProtoWrite>(buffer: &mut W, config: &Config, def: &EnumDef, value: &Value) -> RpResult<()> {
+    let number = match *value {
+        Value::Enum(val, ref name) => {
+            ensure!(def.get_variant_value(name) == Some(val),
+                    (ErrorKind::TypeNotMatchError, "enum name not match the value"));
+            val
+        }
+        Value::U8(val) => val as u32,
+        Value::U16(val) => val as u32,
+        Value::U32(val) => val,
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    };
+    ensure!(def.get_variant_name(number).is_some(),
+            (ErrorKind::ParseError, "unknown enum variant", number.to_string()));
+    let base = unwrap_or!(def.to_base_value(number),
+                          fail!((ErrorKind::TypeNotMatchError, "enum base type must be u8, u16 or u32")));
+    encode_field(buffer, config, &base)
+}
+
+pub fn encode_union<W: ProtoWrite>(buffer: &mut W, config: &Config, def: &UnionDef, value: &Value) -> RpResult<()> {
+    match *value {
+        Value::Union(tag, ref name, ref sub_value) => {
+            let arm = unwrap_or!(def.get_arm_by_name(name),
+                                 fail!((ErrorKind::ParseError, "unknown union arm", name.clone())));
+            ensure!(arm.tag == tag,
+                    (ErrorKind::TypeNotMatchError, "union tag not match the arm"));
+            ensure!(is_match_pattern(config, &arm.pattern, sub_value),
+                    (ErrorKind::TypeNotMatchError, "union payload not match the arm", name.clone()));
+            write_str_field(buffer, STR_TYPE_UNION)?;
+            encode_number(buffer, &Value::U16(tag))?;
+            encode_field_by_pattern(buffer, config, &arm.pattern, sub_value)?;
+        }
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    }
+    Ok(())
+}
+
+pub fn encode_struct<W: ProtoWrite>(buffer: &mut W, config: &Config, def: &StructDef, value: &Value) -> RpResult<()> {
+    match *value {
+        Value::Map(ref val) => {
+            let missing = def.get_missing_names(val);
+            ensure!(missing.is_empty(),
+                    (ErrorKind::MissingError, "missing required fields", missing.join(", ")));
+            write_str_field(buffer, STR_TYPE_MAP)?;
+            for (name, sub_value) in val {
+                let field = unwrap_or!(def.get_field_by_name(name),
+                                       fail!((ErrorKind::ParseError, "unknown struct field", name.clone())));
+                ensure!(is_match_pattern(config, &field.pattern, sub_value),
+                        (ErrorKind::TypeNotMatchError, "struct field not match the pattern", name.clone()));
+                if is_omit_value(config, field, sub_value) {
+                    continue;
+                }
+                encode_number(buffer, &Value::U16(field.index))?;
+                encode_number(buffer, &Value::U16(config.get_pattern_type(&field.pattern)))?;
+                encode_field_by_pattern(buffer, config, &field.pattern, sub_value)?;
+            }
+            write_str_field(buffer, STR_TYPE_NULL)?;
+        }
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    }
+    Ok(())
+}
+
+/// the null elements are written as they are, `encode_field_by_pattern` only lets
+/// them through for a `X?[]` pattern
+pub fn encode_struct_vec<W: ProtoWrite>(buffer: &mut W, config: &Config, def: &StructDef, value: &Value) -> RpResult<()> {
+    match *value {
+        Value::VecMap(ref val) => {
+            write_str_field(buffer, STR_TYPE_VEC_MAP)?;
+            for v in val {
+                match *v {
+                    Value::Null => encode_field(buffer, config, v)?,
+                    _ => encode_struct(buffer, config, def, v)?,
+                }
+            }
+            write_str_field(buffer, STR_TYPE_NULL)?;
+        }
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    }
+    Ok(())
+}
+
+pub fn encode_map<W: ProtoWrite>(buffer: &mut W, config: &Config, value: &Value) -> RpResult<()> {
     match *value {
         Value::Map(ref val) => {
             for (name, sub_value) in val {
-                if write_field(buffer, config.get_field_by_name(name))? {
-                    encode_field(buffer, config, sub_value)?;
+                let field = unwrap_or!(config.get_field_by_name(name), continue);
+                if is_omit_value(config, field, sub_value) {
+                    continue;
                 }
+                encode_number(buffer, &Value::U16(field.index))?;
+                encode_number(buffer, &Value::U16(config.get_pattern_type(&field.pattern)))?;
+                encode_field_by_pattern(buffer, config, &field.pattern, sub_value)?;
             }
             write_str_field(buffer, STR_TYPE_NULL)?;
         }
@@ -84,7 +167,7 @@ pub fn encode_map(buffer: &mut Buffer, config: &Config, value: &Value) -> RpResu
 }
 
 
-pub fn write_field(buffer: &mut Buffer, field: Option<&Field>) -> RpResult<bool> {
+pub fn write_field<W: ProtoWrite>(buffer: &mut W, field: Option<&Field>) -> RpResult<bool> {
     if field.is_none() {
         return Ok(false);
     }
@@ -94,7 +177,7 @@ pub fn write_field(buffer: &mut Buffer, field: Option<&Field>) -> RpResult<bool>
     Ok(true)
 }
 
-pub fn encode_field(buffer: &mut Buffer, config: &Config, value: &Value) -> RpResult<()> {
+pub fn encode_field<W: ProtoWrite>(buffer: &mut W, config: &Config, value: &Value) -> RpResult<()> {
     write_str_field(buffer, get_name_by_type(get_value_type(value)))?;
     match *value {
         Value::U8(_) |
@@ -108,12 +191,18 @@ pub fn encode_field(buffer: &mut Buffer, config: &Config, value: &Value) -> RpRe
             encode_number(buffer, value)?;
         }
         Value::Str(_) | Value::Raw(_) => {
-            encode_str_raw(buffer, value)?;
+            encode_str_raw(buffer, config, value)?;
         }
         Value::Map(_) => {
             encode_map(buffer, config, value)?;
         }
         Value::Null => {}
+        Value::Enum(_, _) => {
+            fail!((ErrorKind::TypeNotMatchError, "enum must encode by the enum pattern"));
+        }
+        Value::Union(_, _, _) => {
+            fail!((ErrorKind::TypeNotMatchError, "union must encode by the union pattern"));
+        }
         Value::VecU8(ref val) |
         Value::VecI8(ref val) |
         Value::VecU16(ref val) |
@@ -127,7 +216,9 @@ pub fn encode_field(buffer: &mut Buffer, config: &Config, value: &Value) -> RpRe
         Value::VecMap(ref val) => {
             let must_type = get_vec_elem_type(value);
             for v in val {
-                check_vailed!(v, must_type);
+                if *v != Value::Null {
+                    check_vailed!(v, must_type);
+                }
                 encode_field(buffer, config, v)?;
             }
             write_str_field(buffer, STR_TYPE_NULL)?;
@@ -136,21 +227,249 @@ pub fn encode_field(buffer: &mut Buffer, config: &Config, value: &Value) -> RpRe
     Ok(())
 }
 
-pub fn encode_proto(buffer: &mut Buffer,
+pub fn encode_field_by_pattern<W: ProtoWrite>(buffer: &mut W,
+                               config: &Config,
+                               pattern: &String,
+                               value: &Value)
+                               -> RpResult<()> {
+    if let Some(inner) = config.get_nullable_inner(pattern) {
+        return match *value {
+            Value::Null => encode_field(buffer, config, value),
+            _ => encode_field_by_pattern(buffer, config, &inner, value),
+        };
+    }
+    if let Some(inner) = config.get_nullable_elem_vec(pattern) {
+        return match config.get_struct_by_vec_pattern(&inner) {
+            Some(def) => encode_struct_vec(buffer, config, def, value),
+            None => encode_field(buffer, config, value),
+        };
+    }
+    ensure!(*value != Value::Null, (ErrorKind::TypeNotMatchError, "null value for a non nullable pattern"));
+    if let Some(def) = config.get_enum_by_name(pattern) {
+        return encode_enum(buffer, config, def, value);
+    }
+    if let Some(def) = config.get_union_by_name(pattern) {
+        return encode_union(buffer, config, def, value);
+    }
+    if let Some(def) = config.get_struct_by_name(pattern) {
+        return encode_struct(buffer, config, def, value);
+    }
+    if let Some(def) = config.get_struct_by_vec_pattern(pattern) {
+        ensure!(!has_null_elem(value), (ErrorKind::TypeNotMatchError, "null element in a non nullable list"));
+        return encode_struct_vec(buffer, config, def, value);
+    }
+    ensure!(!has_null_elem(value), (ErrorKind::TypeNotMatchError, "null element in a non nullable list"));
+    encode_field(buffer, config, value)
+}
+
+fn has_null_elem(value: &Value) -> bool {
+    match *value {
+        Value::VecU8(ref val) |
+        Value::VecI8(ref val) |
+        Value::VecU16(ref val) |
+        Value::VecI16(ref val) |
+        Value::VecU32(ref val) |
+        Value::VecI32(ref val) |
+        Value::VecF32(ref val) |
+        Value::VecF64(ref val) |
+        Value::VecStr(ref val) |
+        Value::VecRaw(ref val) |
+        Value::VecMap(ref val) => val.contains(&Value::Null),
+        _ => false,
+    }
+}
+
+fn is_omit_value(config: &Config, field: &Field, value: &Value) -> bool {
+    config.is_omit_default() && config.get_default_value(field).as_ref() == Some(value)
+}
+
+fn is_match_pattern(config: &Config, pattern: &String, value: &Value) -> bool {
+    if let Some(inner) = config.get_nullable_inner(pattern) {
+        return *value == Value::Null || is_match_pattern(config, &inner, value);
+    }
+    if let Some(inner) = config.get_nullable_elem_vec(pattern) {
+        return is_match_pattern(config, &inner, value);
+    }
+    config.is_schema_pattern(pattern) || get_value_type(value) == get_type_by_name(pattern)
+}
+
+/// the writes are taken back if it fails, so a sink such as a `Buffer::with_max_size`
+/// holds no partial message
+pub fn encode_proto<W: ProtoWrite>(buffer: &mut W,
                     config: &Config,
-                    name: &String,
+                    name: &str,
                     infos: Vec<Value>)
                     -> RpResult<()> {
+    let mut checkpoint = WriteCheckpoint::new(buffer);
+    encode_proto_args(&mut *checkpoint, config, name, infos)?;
+    checkpoint.commit();
+    Ok(())
+}
+
+fn encode_proto_args<W: ProtoWrite>(buffer: &mut W, config: &Config, name: &str, infos: Vec<Value>) -> RpResult<()> {
     let proto = config.get_proto_by_name(name);
     ensure!(proto.is_some(),
             (ErrorKind::MissingError, "missing the name protocol"));
     let proto = proto.unwrap();
     ensure!(proto.args.len() == infos.len(),
             (ErrorKind::TypeNotMatchError, "the data num not match protocol args num"));
-    encode_str_raw(buffer, &Value::Str(name.clone()))?;
-    for info in &infos {
-        encode_field(buffer, config, info)?;
+    encode_str_raw(buffer, config, &Value::Str(name.to_string()))?;
+    for (pattern, info) in proto.args.iter().zip(&infos) {
+        encode_field_by_pattern(buffer, config, pattern, info)?;
     }
     write_str_field(buffer, STR_TYPE_NULL)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// the index and the type before every encoded value
+const FIELD_HEADER_LEN: usize = 4;
+
+fn number_len(value: &Value) -> usize {
+    match *value {
+        Value::U8(_) | Value::I8(_) => 1,
+        Value::U16(_) | Value::I16(_) => 2,
+        Value::U32(_) | Value::I32(_) | Value::F32(_) => 4,
+        Value::F64(_) => 8,
+        _ => unreachable!("number_len only"),
+    }
+}
+
+fn str_raw_len(config: &Config, len: usize) -> usize {
+    2 + len + config.get_padding_len(len)
+}
+
+/// the bytes `encode_field` writes for the value
+pub fn encoded_len(config: &Config, value: &Value) -> RpResult<usize> {
+    let len = match *value {
+        Value::U8(_) |
+        Value::I8(_) |
+        Value::U16(_) |
+        Value::I16(_) |
+        Value::U32(_) |
+        Value::I32(_) |
+        Value::F32(_) |
+        Value::F64(_) => number_len(value),
+        Value::Str(ref val) => str_raw_len(config, val.len()),
+        Value::Raw(ref val) => str_raw_len(config, val.len()),
+        Value::Map(ref val) => {
+            let mut len = FIELD_HEADER_LEN;
+            for (name, sub_value) in val {
+                let field = unwrap_or!(config.get_field_by_name(name), continue);
+                if is_omit_value(config, field, sub_value) {
+                    continue;
+                }
+                len += FIELD_HEADER_LEN + encoded_len_by_pattern(config, &field.pattern, sub_value)?;
+            }
+            len
+        }
+        Value::Null => 0,
+        Value::Enum(_, _) => {
+            fail!((ErrorKind::TypeNotMatchError, "enum must encode by the enum pattern"));
+        }
+        Value::Union(_, _, _) => {
+            fail!((ErrorKind::TypeNotMatchError, "union must encode by the union pattern"));
+        }
+        Value::VecU8(ref val) |
+        Value::VecI8(ref val) |
+        Value::VecU16(ref val) |
+        Value::VecI16(ref val) |
+        Value::VecU32(ref val) |
+        Value::VecI32(ref val) |
+        Value::VecF32(ref val) |
+        Value::VecF64(ref val) |
+        Value::VecStr(ref val) |
+        Value::VecRaw(ref val) |
+        Value::VecMap(ref val) => {
+            let mut len = FIELD_HEADER_LEN;
+            for v in val {
+                len += encoded_len(config, v)?;
+            }
+            len
+        }
+    };
+    Ok(FIELD_HEADER_LEN + len)
+}
+
+/// the bytes `encode_field_by_pattern` writes for the value
+pub fn encoded_len_by_pattern(config: &Config, pattern: &String, value: &Value) -> RpResult<usize> {
+    if let Some(inner) = config.get_nullable_inner(pattern) {
+        return match *value {
+            Value::Null => encoded_len(config, value),
+            _ => encoded_len_by_pattern(config, &inner, value),
+        };
+    }
+    if let Some(inner) = config.get_nullable_elem_vec(pattern) {
+        return encoded_len_by_pattern(config, &inner, value);
+    }
+    if let Some(def) = config.get_enum_by_name(pattern) {
+        let number = match *value {
+            Value::Enum(val, _) => val,
+            Value::U8(val) => val as u32,
+            Value::U16(val) => val as u32,
+            Value::U32(val) => val,
+            _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+        };
+        let base = unwrap_or!(def.to_base_value(number),
+                              fail!((ErrorKind::TypeNotMatchError, "enum base type must be u8, u16 or u32")));
+        return encoded_len(config, &base);
+    }
+    if let Some(def) = config.get_union_by_name(pattern) {
+        return match *value {
+            Value::Union(_, ref name, ref sub_value) => {
+                let arm = unwrap_or!(def.get_arm_by_name(name),
+                                     fail!((ErrorKind::ParseError, "unknown union arm", name.clone())));
+                Ok(FIELD_HEADER_LEN + 2 + encoded_len_by_pattern(config, &arm.pattern, sub_value)?)
+            }
+            _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+        };
+    }
+    if let Some(def) = config.get_struct_by_name(pattern) {
+        return encoded_struct_len(config, def, value);
+    }
+    if let Some(def) = config.get_struct_by_vec_pattern(pattern) {
+        return match *value {
+            Value::VecMap(ref val) => {
+                let mut len = FIELD_HEADER_LEN + FIELD_HEADER_LEN;
+                for v in val {
+                    len += match *v {
+                        Value::Null => encoded_len(config, v)?,
+                        _ => encoded_struct_len(config, def, v)?,
+                    };
+                }
+                Ok(len)
+            }
+            _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+        };
+    }
+    encoded_len(config, value)
+}
+
+fn encoded_struct_len(config: &Config, def: &StructDef, value: &Value) -> RpResult<usize> {
+    let val = match *value {
+        Value::Map(ref val) => val,
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    };
+    let mut len = FIELD_HEADER_LEN + FIELD_HEADER_LEN;
+    for (name, sub_value) in val {
+        let field = unwrap_or!(def.get_field_by_name(name),
+                               fail!((ErrorKind::ParseError, "unknown struct field", name.clone())));
+        if is_omit_value(config, field, sub_value) {
+            continue;
+        }
+        len += FIELD_HEADER_LEN + encoded_len_by_pattern(config, &field.pattern, sub_value)?;
+    }
+    Ok(len)
+}
+
+/// the bytes `encode_proto` writes for the message
+pub fn encoded_proto_len(config: &Config, name: &str, infos: &[Value]) -> RpResult<usize> {
+    let proto = unwrap_or!(config.get_proto_by_name(name),
+                           fail!((ErrorKind::MissingError, "missing the name protocol")));
+    ensure!(proto.args.len() == infos.len(),
+            (ErrorKind::TypeNotMatchError, "the data num not match protocol args num"));
+    let mut len = str_raw_len(config, name.len()) + FIELD_HEADER_LEN;
+    for (pattern, info) in proto.args.iter().zip(infos) {
+        len += encoded_len_by_pattern(config, pattern, info)?;
+    }
+    Ok(len)
+}
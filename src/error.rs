@@ -6,6 +6,8 @@ use std::fmt::Display;
 pub enum ErrorKind {
     /// will read bytes over than left bytes
     NoLeftSpaceError,
+    /// the buffer doesn't hold the whole message yet
+    IncompleteError,
     /// the buffer over max
     BufferOverMaxError,
     /// The type not match
@@ -16,6 +18,12 @@ pub enum ErrorKind {
     MissingError,
     /// string format must be utf-8
     StringFormatError,
+    /// the frame payload can't be compressed or decompressed
+    CompressError,
+    /// the frame checksum doesn't match its bytes
+    ChecksumError,
+    /// the sealed frame fails to open, it was tampered, replayed or sealed with another key
+    SealError,
     /// This kind is returned if the redis error is one that is
     /// not native to the system.  This is usually the case if
     /// the cause is another error.
@@ -62,6 +70,10 @@ impl PartialEq for RpError {
 
 impl From<io::Error> for RpError {
     fn from(err: io::Error) -> RpError {
+        // errors of the library which pass through `Read` or `Write` keep their kind
+        if err.get_ref().is_some_and(|inner| inner.is::<RpError>()) {
+            return *err.into_inner().unwrap().downcast::<RpError>().unwrap();
+        }
         RpError { repr: ErrorRepr::IoError(err) }
     }
 }
@@ -138,11 +150,15 @@ impl RpError {
     pub fn category(&self) -> &str {
         match self.kind() {
             ErrorKind::NoLeftSpaceError => "no left space error",
+            ErrorKind::IncompleteError => "incomplete error",
             ErrorKind::BufferOverMaxError => "buffer over max error",
             ErrorKind::TypeNotMatchError => "type not match error",
             ErrorKind::ParseError => "parse error",
             ErrorKind::MissingError => "missing error",
             ErrorKind::StringFormatError => "string format error",
+            ErrorKind::CompressError => "compress error",
+            ErrorKind::ChecksumError => "checksum error",
+            ErrorKind::SealError => "seal error",
             ErrorKind::IoError => "I/O error",
             ErrorKind::ExtensionError => "extension error",
         }
@@ -156,6 +172,14 @@ impl RpError {
         }
     }
 
+    /// Indicates that more bytes are needed to decode the message.
+    pub fn is_incomplete(&self) -> bool {
+        match self.kind() {
+            ErrorKind::IncompleteError => true,
+            _ => false,
+        }
+    }
+
     /// Returns the extension error code
     pub fn extension_error_code(&self) -> Option<&str> {
         match self.repr {
@@ -0,0 +1,388 @@
+use std::borrow::Cow;
+use crate::value::*;
+use crate::{Buffer};
+use crate::error::*;
+use crate::config::Config;
+use crate::encode::{encode_number, encode_proto, encoded_proto_len};
+use crate::proto_io::{ProtoWrite, ProtoRead, Checkpoint, WriteCheckpoint};
+use crate::decode::decode_proto;
+use crate::compress::{Compression, compress, decompress};
+use crate::checksum::{Checksum, CHECKSUM_LEN, compute_checksum};
+#[cfg(feature = "seal")]
+use crate::seal::{Sealer, SEAL_TAG_LEN};
+
+/// the frame header carries a sequence number
+pub const FRAME_FLAG_SEQ: u8 = 0x01;
+/// the payload is compressed with lz4
+pub const FRAME_FLAG_LZ4: u8 = 0x02;
+/// the payload is compressed with zstd
+pub const FRAME_FLAG_ZSTD: u8 = 0x04;
+/// the payload ends with a crc32 of the header and the rest of the payload
+pub const FRAME_FLAG_CRC32: u8 = 0x08;
+/// the payload ends with an xxh32 of the header and the rest of the payload
+pub const FRAME_FLAG_XXHASH: u8 = 0x10;
+/// the payload is encrypted and ends with the tag, the header is authenticated
+pub const FRAME_FLAG_SEALED: u8 = 0x20;
+
+/// payloads shorter than this are sent as they are
+pub const DEFAULT_COMPRESS_THRESHOLD: usize = 1024;
+/// a compressed frame may not claim a larger payload than this
+pub const DEFAULT_MAX_DECOMPRESS_LEN: usize = 16 * 1024 * 1024;
+
+/// length u32 and flags u8, the sequence number u32 follows if `FRAME_FLAG_SEQ` is set
+pub const FRAME_HEADER_LEN: usize = 5;
+pub const FRAME_SEQ_LEN: usize = 4;
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct FrameHeader {
+    pub length: u32,
+    pub flags: u8,
+    pub seq: Option<u32>,
+}
+
+/// the framing settings agreed with the peer of one connection
+#[derive(Debug)]
+pub struct FrameSession {
+    checksum: Checksum,
+    #[cfg(feature = "seal")]
+    sealer: Option<Sealer>,
+    compression: Compression,
+    compress_threshold: usize,
+    max_decompress_len: usize,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum FrameState {
+    /// the buffer needs at least these more bytes before the frame is complete
+    NeedMore(usize),
+    /// a whole frame is buffered after the read position
+    Ready(FrameHeader),
+}
+
+impl FrameHeader {
+    pub fn new(length: u32, seq: Option<u32>) -> FrameHeader {
+        FrameHeader {
+            length,
+            flags: if seq.is_some() { FRAME_FLAG_SEQ } else { 0 },
+            seq,
+        }
+    }
+
+    pub fn has_flag(&self, flag: u8) -> bool {
+        self.flags & flag == flag
+    }
+
+    /// the bytes of the header itself
+    pub fn header_len(&self) -> usize {
+        match self.seq {
+            Some(_) => FRAME_HEADER_LEN + FRAME_SEQ_LEN,
+            None => FRAME_HEADER_LEN,
+        }
+    }
+
+    /// the bytes of the header and the payload
+    pub fn frame_len(&self) -> usize {
+        self.header_len() + self.length as usize
+    }
+}
+
+impl FrameSession {
+    pub fn new() -> FrameSession {
+        FrameSession {
+            checksum: Checksum::None,
+            #[cfg(feature = "seal")]
+            sealer: None,
+            compression: Compression::None,
+            compress_threshold: DEFAULT_COMPRESS_THRESHOLD,
+            max_decompress_len: DEFAULT_MAX_DECOMPRESS_LEN,
+        }
+    }
+
+    /// the outgoing frames carry the checksum, the incoming frames without one
+    /// fail with `ChecksumError`
+    pub fn set_checksum(&mut self, checksum: Checksum) {
+        self.checksum = checksum;
+    }
+
+    pub fn get_checksum(&self) -> Checksum {
+        self.checksum
+    }
+
+    /// the outgoing frames are sealed and the incoming frames must be sealed,
+    /// compressed payloads are sealed after the compression
+    #[cfg(feature = "seal")]
+    pub fn set_sealer(&mut self, sealer: Sealer) {
+        self.sealer = Some(sealer);
+    }
+
+    #[cfg(feature = "seal")]
+    pub fn get_sealer(&self) -> Option<&Sealer> {
+        self.sealer.as_ref()
+    }
+
+    /// the tag bytes appended to the outgoing payloads
+    #[cfg(feature = "seal")]
+    fn seal_len(&self) -> usize {
+        if self.sealer.is_some() { SEAL_TAG_LEN } else { 0 }
+    }
+
+    #[cfg(not(feature = "seal"))]
+    fn seal_len(&self) -> usize {
+        0
+    }
+
+    /// frames whose payload reaches the threshold are compressed, the receiver
+    /// follows the frame flags whatever its own compression is
+    pub fn set_compression(&mut self, compression: Compression, threshold: usize) {
+        self.compression = compression;
+        self.compress_threshold = threshold;
+    }
+
+    pub fn get_compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub fn get_compress_threshold(&self) -> usize {
+        self.compress_threshold
+    }
+
+    pub fn set_max_decompress_len(&mut self, max_decompress_len: usize) {
+        self.max_decompress_len = max_decompress_len;
+    }
+
+    pub fn get_max_decompress_len(&self) -> usize {
+        self.max_decompress_len
+    }
+}
+
+impl Default for FrameSession {
+    fn default() -> FrameSession {
+        FrameSession::new()
+    }
+}
+
+pub fn write_frame_header<W: ProtoWrite>(buffer: &mut W, header: &FrameHeader) -> RpResult<()> {
+    encode_number(buffer, &Value::U32(header.length))?;
+    encode_number(buffer, &Value::U8(header.flags))?;
+    if let Some(seq) = header.seq {
+        encode_number(buffer, &Value::U32(seq))?;
+    }
+    Ok(())
+}
+
+fn frame_header_bytes(header: &FrameHeader) -> RpResult<Vec<u8>> {
+    let mut data = Vec::with_capacity(header.header_len());
+    write_frame_header(&mut data, header)?;
+    Ok(data)
+}
+
+pub fn encode_frame<W: ProtoWrite>(buffer: &mut W,
+                                   config: &Config,
+                                   name: &str,
+                                   infos: Vec<Value>,
+                                   seq: Option<u32>)
+                                   -> RpResult<()> {
+    encode_frame_with(buffer, config, &mut FrameSession::new(), name, infos, seq)
+}
+
+pub fn encode_frame_with<W: ProtoWrite>(buffer: &mut W,
+                                        config: &Config,
+                                        session: &mut FrameSession,
+                                        name: &str,
+                                        infos: Vec<Value>,
+                                        seq: Option<u32>)
+                                        -> RpResult<()> {
+    // the payload is encoded before anything is written, so a value the encoder
+    // rejects leaves no partial frame behind
+    let mut payload = Vec::with_capacity(encoded_proto_len(config, name, &infos)?);
+    encode_proto(&mut payload, config, name, infos)?;
+    let compression = session.get_compression();
+    let should_compress = compression != Compression::None && payload.len() >= session.get_compress_threshold();
+    let checksum = session.get_checksum();
+    let seal_len = session.seal_len();
+    let mut header = FrameHeader::new(0, seq);
+    if should_compress {
+        let compressed = compress(compression, &payload)?;
+        // the data which doesn't shrink is sent as it is
+        if compressed.len() < payload.len() {
+            header.flags |= compression.get_flag();
+            payload = compressed;
+        }
+    }
+    header.length = payload.len() as u32;
+    if seal_len > 0 {
+        header.flags |= FRAME_FLAG_SEALED;
+        header.length += seal_len as u32;
+    }
+    if checksum != Checksum::None {
+        header.flags |= checksum.get_flag();
+        header.length += CHECKSUM_LEN as u32;
+    }
+
+    // the whole header is known before the payload is sealed or summed
+    let header_bytes = frame_header_bytes(&header)?;
+    #[cfg(feature = "seal")]
+    {
+        if let Some(sealer) = session.sealer.as_mut() {
+            sealer.seal(&header_bytes, &mut payload)?;
+        }
+    }
+    if checksum != Checksum::None {
+        let value = compute_checksum(checksum, &[&header_bytes, &payload])?;
+        payload.extend_from_slice(&value.to_le_bytes());
+    }
+    let mut checkpoint = WriteCheckpoint::new(buffer);
+    write_frame_header(&mut *checkpoint, &header)?;
+    checkpoint.write_bytes(&payload)?;
+    checkpoint.commit();
+    Ok(())
+}
+
+/// check whether a whole frame is buffered, the buffer is never consumed
+pub fn check_frame(buffer: &Buffer, config: &Config) -> RpResult<FrameState> {
+    check_frame_bytes(&buffer.get_data()[buffer.get_read_pos()..], config)
+}
+
+/// check whether the bytes start with a whole frame
+pub fn check_frame_bytes(data: &[u8], config: &Config) -> RpResult<FrameState> {
+    if data.len() < FRAME_HEADER_LEN {
+        return Ok(FrameState::NeedMore(FRAME_HEADER_LEN - data.len()));
+    }
+    check_frame_len(data, config)?;
+    if data[4] & FRAME_FLAG_SEQ != 0 && data.len() < FRAME_HEADER_LEN + FRAME_SEQ_LEN {
+        return Ok(FrameState::NeedMore(FRAME_HEADER_LEN + FRAME_SEQ_LEN - data.len()));
+    }
+
+    let header = parse_frame_header(data);
+    if data.len() < header.frame_len() {
+        return Ok(FrameState::NeedMore(header.frame_len() - data.len()));
+    }
+    Ok(FrameState::Ready(header))
+}
+
+/// the length is checked before anything of the payload is buffered
+fn check_frame_len(data: &[u8], config: &Config) -> RpResult<()> {
+    let mut length = [0; 4];
+    length.copy_from_slice(&data[..4]);
+    let length = u32::from_le_bytes(length) as usize;
+    ensure!(length <= config.get_max_frame_len(),
+            (ErrorKind::BufferOverMaxError, "the frame is larger than the max frame length", length.to_string()));
+    Ok(())
+}
+
+/// the data holds the whole header, the sequence number included
+fn parse_frame_header(data: &[u8]) -> FrameHeader {
+    let mut length = [0; 4];
+    length.copy_from_slice(&data[..4]);
+    let mut header = FrameHeader {
+        length: u32::from_le_bytes(length),
+        flags: data[4],
+        seq: None,
+    };
+    if header.has_flag(FRAME_FLAG_SEQ) {
+        let mut seq = [0; 4];
+        seq.copy_from_slice(&data[FRAME_HEADER_LEN..FRAME_HEADER_LEN + FRAME_SEQ_LEN]);
+        header.seq = Some(u32::from_le_bytes(seq));
+    }
+    header
+}
+
+/// the next frame and its payload, None if the reader doesn't hold the whole frame
+/// yet, the reader is left at the frame start then
+pub fn read_frame<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<Option<(FrameHeader, Vec<u8>)>> {
+    let mut checkpoint = Checkpoint::new(buffer);
+    match read_frame_parts(&mut *checkpoint, config) {
+        Ok(frame) => {
+            checkpoint.commit();
+            Ok(Some(frame))
+        }
+        Err(ref err) if err.kind() == ErrorKind::NoLeftSpaceError => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_frame_parts<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<(FrameHeader, Vec<u8>)> {
+    let mut data = [0; FRAME_HEADER_LEN + FRAME_SEQ_LEN];
+    buffer.read_fill(&mut data[..FRAME_HEADER_LEN])?;
+    check_frame_len(&data, config)?;
+    if data[4] & FRAME_FLAG_SEQ != 0 {
+        buffer.read_fill(&mut data[FRAME_HEADER_LEN..])?;
+    }
+    let header = parse_frame_header(&data);
+    let mut payload = vec![0; header.length as usize];
+    buffer.read_fill(&mut payload)?;
+    Ok((header, payload))
+}
+
+pub fn decode_frame<R: ProtoRead>(buffer: &mut R, config: &Config) -> RpResult<Option<(FrameHeader, String, Vec<Value>)>> {
+    decode_frame_with(buffer, config, &mut FrameSession::new())
+}
+
+pub fn decode_frame_with<R: ProtoRead>(buffer: &mut R,
+                                       config: &Config,
+                                       session: &mut FrameSession)
+                         -> RpResult<Option<(FrameHeader, String, Vec<Value>)>> {
+    let (header, payload) = unwrap_or!(read_frame(buffer, config)?, return Ok(None));
+    let (name, infos) = decode_frame_body(config, session, &header, &payload)?;
+    Ok(Some((header, name, infos)))
+}
+
+/// check the trailer of the payload and return the payload without it
+pub fn verify_frame_checksum<'a>(session: &FrameSession, header: &FrameHeader, payload: &'a [u8]) -> RpResult<&'a [u8]> {
+    let checksum = Checksum::from_flags(header.flags)?;
+    if checksum == Checksum::None {
+        ensure!(session.get_checksum() == Checksum::None, (ErrorKind::ChecksumError, "frame has no checksum"));
+        return Ok(payload);
+    }
+
+    ensure!(payload.len() >= CHECKSUM_LEN, (ErrorKind::ChecksumError, "frame is too short for the checksum"));
+    let (body, trailer) = payload.split_at(payload.len() - CHECKSUM_LEN);
+    let mut expect = [0; CHECKSUM_LEN];
+    expect.copy_from_slice(trailer);
+    let value = compute_checksum(checksum, &[&frame_header_bytes(header)?, body])?;
+    ensure!(value == u32::from_le_bytes(expect), (ErrorKind::ChecksumError, "frame checksum not match"));
+    Ok(body)
+}
+
+/// decrypt a sealed payload, a tampered, replayed or unexpected frame fails with `SealError`,
+/// a sealed frame always fails without the `seal` feature
+#[cfg_attr(not(feature = "seal"), allow(unused_variables))]
+pub fn open_frame<'a>(session: &mut FrameSession, header: &FrameHeader, payload: &'a [u8]) -> RpResult<Cow<'a, [u8]>> {
+    let sealed = header.has_flag(FRAME_FLAG_SEALED);
+    #[cfg(feature = "seal")]
+    {
+        if let Some(sealer) = session.sealer.as_mut() {
+            ensure!(sealed, (ErrorKind::SealError, "frame is not sealed"));
+            let mut data = payload.to_vec();
+            sealer.open(&frame_header_bytes(header)?, &mut data)?;
+            return Ok(Cow::Owned(data));
+        }
+    }
+    ensure!(!sealed, (ErrorKind::SealError, "no key to open the sealed frame"));
+    Ok(Cow::Borrowed(payload))
+}
+
+/// decode the payload of the frame, the checksum is checked, then a sealed payload
+/// is opened and a compressed payload is decompressed
+pub fn decode_frame_body(config: &Config,
+                         session: &mut FrameSession,
+                         header: &FrameHeader,
+                         payload: &[u8])
+                         -> RpResult<(String, Vec<Value>)> {
+    let payload = verify_frame_checksum(session, header, payload)?;
+    let payload = open_frame(session, header, payload)?;
+    match Compression::from_flags(header.flags)? {
+        Compression::None => decode_frame_payload(config, &payload),
+        compression => {
+            let payload = decompress(compression, &payload, session.get_max_decompress_len())?;
+            decode_frame_payload(config, &payload)
+        }
+    }
+}
+
+pub fn decode_frame_payload(config: &Config, payload: &[u8]) -> RpResult<(String, Vec<Value>)> {
+    let mut reader = payload;
+    let result = decode_proto(&mut reader, config)?;
+    ensure!(reader.is_empty(), (ErrorKind::ParseError, "frame payload has trailing bytes"));
+    Ok(result)
+}
@@ -2,9 +2,24 @@ mod macro_use;
 pub mod encode;
 pub mod config;
 pub mod buffer;
+pub mod proto_io;
+pub mod pool;
+pub mod ring;
 pub mod decode;
+pub mod decode_ref;
 pub mod error;
+pub mod frame;
+pub mod compress;
+pub mod checksum;
+#[cfg(feature = "seal")]
+pub mod seal;
+pub mod stream;
+#[cfg(feature = "tokio")]
+pub mod codec;
 pub mod value;
+pub mod value_ref;
+pub mod view;
 
 pub use buffer::Buffer;
-pub use value::*;
\ No newline at end of file
+pub use value::*;
+pub use value_ref::ValueRef;
\ No newline at end of file
@@ -22,37 +22,6 @@ macro_rules! unwrap_or {
     )
 }
 
-macro_rules! try_read {
-    ($expr:expr, $val:expr) => (
-        {
-            if $expr? != $val {
-                fail!((ErrorKind::NoLeftSpaceError, "must left space to read "));
-            }
-        }
-    )
-}
-
-macro_rules! decode_array {
-    ($expr:expr, $path:path, $match_path:path) => (
-        {
-            let mut value : Vec<Value> = vec![];
-            loop {
-                let sub_value = $expr?;
-                match sub_value {
-                    Value::Null => {
-                        break;
-                    }
-                    $match_path(_) => {}
-                    _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
-                }
-                value.push(sub_value);
-            }
-            Ok($path(value))
-        }
-    )
-}
-
-
 macro_rules! check_vailed {
     ($value:ident, $expr:expr) => (
         {
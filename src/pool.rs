@@ -0,0 +1,135 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::{Buffer};
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct PoolStats {
+    /// `get` calls served by a pooled buffer
+    pub hits: usize,
+    /// `get` calls which had to allocate a new buffer
+    pub misses: usize,
+    /// the buffers waiting in the pool
+    pub pooled: usize,
+    /// the capacity kept by the waiting buffers
+    pub bytes_retained: usize,
+}
+
+struct PoolInner {
+    buffers: Mutex<Vec<Buffer>>,
+    pool_size: usize,
+    max_capacity: usize,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl PoolInner {
+    fn lock(&self) -> MutexGuard<'_, Vec<Buffer>> {
+        // a panic while holding the lock can't leave the vec half updated
+        self.buffers.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn release(&self, mut buffer: Buffer) {
+        if buffer.capacity() > self.max_capacity {
+            return;
+        }
+        let mut buffers = self.lock();
+        if buffers.len() < self.pool_size {
+            buffer.clear();
+            buffer.set_max_size(usize::MAX);
+            buffers.push(buffer);
+        }
+    }
+}
+
+/// hands out cleared buffers which keep their capacity, the buffers go back to the
+/// pool on drop, clones share the same pool
+#[derive(Clone)]
+pub struct BufferPool {
+    inner: Arc<PoolInner>,
+}
+
+impl BufferPool {
+    /// keep at most `pool_size` buffers, buffers grown over `max_capacity` are freed
+    pub fn new(pool_size: usize, max_capacity: usize) -> BufferPool {
+        BufferPool {
+            inner: Arc::new(PoolInner {
+                buffers: Mutex::new(Vec::new()),
+                pool_size,
+                max_capacity,
+                hits: AtomicUsize::new(0),
+                misses: AtomicUsize::new(0),
+            }),
+        }
+    }
+
+    pub fn get(&self) -> PooledBuffer {
+        let buffer = match self.inner.lock().pop() {
+            Some(buffer) => {
+                self.inner.hits.fetch_add(1, Ordering::Relaxed);
+                buffer
+            }
+            None => {
+                self.inner.misses.fetch_add(1, Ordering::Relaxed);
+                Buffer::new()
+            }
+        };
+        PooledBuffer {
+            buffer: Some(buffer),
+            pool: self.inner.clone(),
+        }
+    }
+
+    pub fn get_pool_size(&self) -> usize {
+        self.inner.pool_size
+    }
+
+    pub fn get_max_capacity(&self) -> usize {
+        self.inner.max_capacity
+    }
+
+    pub fn stats(&self) -> PoolStats {
+        let buffers = self.inner.lock();
+        PoolStats {
+            hits: self.inner.hits.load(Ordering::Relaxed),
+            misses: self.inner.misses.load(Ordering::Relaxed),
+            pooled: buffers.len(),
+            bytes_retained: buffers.iter().map(|buffer| buffer.capacity()).sum(),
+        }
+    }
+}
+
+/// a buffer borrowed from a `BufferPool`
+pub struct PooledBuffer {
+    buffer: Option<Buffer>,
+    pool: Arc<PoolInner>,
+}
+
+impl PooledBuffer {
+    /// take the buffer out, it doesn't go back to the pool
+    pub fn into_inner(mut self) -> Buffer {
+        self.buffer.take().unwrap()
+    }
+}
+
+impl Deref for PooledBuffer {
+    type Target = Buffer;
+
+    fn deref(&self) -> &Buffer {
+        self.buffer.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for PooledBuffer {
+    fn deref_mut(&mut self) -> &mut Buffer {
+        self.buffer.as_mut().unwrap()
+    }
+}
+
+impl Drop for PooledBuffer {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.buffer.take() {
+            self.pool.release(buffer);
+        }
+    }
+}
@@ -0,0 +1,495 @@
+use std::io::{self, Cursor, Read, Write};
+use std::ops::{Deref, DerefMut};
+use crate::{Buffer};
+use crate::ring::RingBuffer;
+use crate::decode_ref::SliceReader;
+use crate::error::*;
+#[cfg(feature = "bytes")]
+use bytes::BytesMut;
+
+/// `IoReader` drops the consumed bytes once this many are buffered
+const IO_COMPACT_LEN: usize = 4096;
+const IO_READ_CHUNK_LEN: usize = 4096;
+
+/// the sink the encoders write to
+pub trait ProtoWrite {
+    /// what is needed to take back the writes after the current position
+    type WriteMark;
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()>;
+
+    fn write_mark(&mut self) -> Self::WriteMark;
+
+    /// drop the writes made since the mark, a sink which sends the bytes away
+    /// can't take them back
+    fn write_reset(&mut self, mark: Self::WriteMark);
+}
+
+/// the source the decoders read from, a failed read consumes nothing so the
+/// decoders can wait for more bytes
+pub trait ProtoRead {
+    /// what is needed to go back to the current position
+    type Mark;
+
+    /// fill the whole buf or fail with `NoLeftSpaceError`
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()>;
+
+    /// skip the next n bytes or fail with `NoLeftSpaceError`
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()>;
+
+    /// every mark ends with either `reset` or `release`
+    fn mark(&mut self) -> Self::Mark;
+
+    /// go back to the position of the mark
+    fn reset(&mut self, mark: Self::Mark);
+
+    /// the reads after the mark are kept, the reader may drop the consumed bytes
+    fn release(&mut self, _mark: Self::Mark) {}
+}
+
+/// restores the read position when it drops unless it is committed, derefs to
+/// the reader so the reads go through the guard
+pub struct Checkpoint<'a, R: ProtoRead + ?Sized> {
+    reader: &'a mut R,
+    mark: Option<R::Mark>,
+}
+
+impl<'a, R: ProtoRead + ?Sized> Checkpoint<'a, R> {
+    pub fn new(reader: &'a mut R) -> Checkpoint<'a, R> {
+        Checkpoint {
+            mark: Some(reader.mark()),
+            reader,
+        }
+    }
+
+    /// keep the reads made since the checkpoint
+    pub fn commit(mut self) {
+        if let Some(mark) = self.mark.take() {
+            self.reader.release(mark);
+        }
+    }
+}
+
+impl<'a, R: ProtoRead + ?Sized> Deref for Checkpoint<'a, R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        self.reader
+    }
+}
+
+impl<'a, R: ProtoRead + ?Sized> DerefMut for Checkpoint<'a, R> {
+    fn deref_mut(&mut self) -> &mut R {
+        self.reader
+    }
+}
+
+impl<'a, R: ProtoRead + ?Sized> Drop for Checkpoint<'a, R> {
+    fn drop(&mut self) {
+        if let Some(mark) = self.mark.take() {
+            self.reader.reset(mark);
+        }
+    }
+}
+
+/// drops the writes made through the guard when it drops unless it is committed,
+/// so a failed encode leaves no partial message behind
+pub struct WriteCheckpoint<'a, W: ProtoWrite + ?Sized> {
+    writer: &'a mut W,
+    mark: Option<W::WriteMark>,
+}
+
+impl<'a, W: ProtoWrite + ?Sized> WriteCheckpoint<'a, W> {
+    pub fn new(writer: &'a mut W) -> WriteCheckpoint<'a, W> {
+        WriteCheckpoint {
+            mark: Some(writer.write_mark()),
+            writer,
+        }
+    }
+
+    /// keep the writes made since the checkpoint
+    pub fn commit(mut self) {
+        self.mark = None;
+    }
+}
+
+impl<'a, W: ProtoWrite + ?Sized> Deref for WriteCheckpoint<'a, W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.writer
+    }
+}
+
+impl<'a, W: ProtoWrite + ?Sized> DerefMut for WriteCheckpoint<'a, W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.writer
+    }
+}
+
+impl<'a, W: ProtoWrite + ?Sized> Drop for WriteCheckpoint<'a, W> {
+    fn drop(&mut self) {
+        if let Some(mark) = self.mark.take() {
+            self.writer.write_reset(mark);
+        }
+    }
+}
+
+impl ProtoRead for Buffer {
+    type Mark = usize;
+
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        Buffer::read_fill(self, buf)
+    }
+
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()> {
+        Buffer::advance(self, n)
+    }
+
+    fn mark(&mut self) -> usize {
+        self.get_read_pos()
+    }
+
+    fn reset(&mut self, mark: usize) {
+        self.set_read_pos(mark)
+    }
+}
+
+impl ProtoRead for RingBuffer {
+    type Mark = usize;
+
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        RingBuffer::read_fill(self, buf)
+    }
+
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()> {
+        RingBuffer::advance(self, n)
+    }
+
+    fn mark(&mut self) -> usize {
+        self.get_read_pos()
+    }
+
+    fn reset(&mut self, mark: usize) {
+        self.set_read_pos(mark)
+    }
+}
+
+/// the slice is advanced past the decoded bytes
+impl<'a> ProtoRead for &'a [u8] {
+    type Mark = &'a [u8];
+
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        ensure!(buf.len() <= self.len(), (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        buf.copy_from_slice(&self[..buf.len()]);
+        *self = &self[buf.len()..];
+        Ok(())
+    }
+
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()> {
+        ensure!(n <= self.len(), (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        *self = &self[n..];
+        Ok(())
+    }
+
+    fn mark(&mut self) -> &'a [u8] {
+        *self
+    }
+
+    fn reset(&mut self, mark: &'a [u8]) {
+        *self = mark;
+    }
+}
+
+impl<'a> ProtoRead for SliceReader<'a> {
+    type Mark = usize;
+
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        buf.copy_from_slice(self.read_bytes(buf.len())?);
+        Ok(())
+    }
+
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()> {
+        self.read_bytes(n)?;
+        Ok(())
+    }
+
+    fn mark(&mut self) -> usize {
+        self.get_pos()
+    }
+
+    fn reset(&mut self, mark: usize) {
+        self.set_pos(mark)
+    }
+}
+
+impl<T: AsRef<[u8]>> ProtoRead for Cursor<T> {
+    type Mark = u64;
+
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        let start = self.position() as usize;
+        self.skip_bytes(buf.len())?;
+        buf.copy_from_slice(&self.get_ref().as_ref()[start..start + buf.len()]);
+        Ok(())
+    }
+
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()> {
+        let len = self.get_ref().as_ref().len() as u64;
+        ensure!(self.position() <= len && n as u64 <= len - self.position(),
+                (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        let pos = self.position() + n as u64;
+        self.set_position(pos);
+        Ok(())
+    }
+
+    fn mark(&mut self) -> u64 {
+        self.position()
+    }
+
+    fn reset(&mut self, mark: u64) {
+        self.set_position(mark);
+    }
+}
+
+/// decode from any `Read`, the reads block until enough bytes come and the bytes
+/// are kept so a failed decode can go back, `NoLeftSpaceError` means the stream ended
+pub struct IoReader<R: Read> {
+    reader: R,
+    buffer: Buffer,
+    marks: usize,
+    /// the read position of the outermost mark
+    start: usize,
+    max_len: usize,
+}
+
+impl<R: Read> IoReader<R> {
+    pub fn new(reader: R) -> IoReader<R> {
+        IoReader {
+            reader,
+            buffer: Buffer::new(),
+            marks: 0,
+            start: 0,
+            max_len: usize::MAX,
+        }
+    }
+
+    /// a decode which needs more than this many bytes from its outermost mark fails
+    /// with `BufferOverMaxError` instead of buffering them
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
+
+    pub fn get_max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// the bytes read from the stream but not decoded yet
+    pub fn buffered_len(&self) -> usize {
+        self.buffer.remaining()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.reader
+    }
+
+    /// the buffered bytes are dropped
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// whether the stream ended and every byte has been read, it blocks until a
+    /// byte comes otherwise
+    pub fn is_eof(&mut self) -> RpResult<bool> {
+        match self.fill_to(1) {
+            Ok(()) => Ok(false),
+            Err(ref err) if err.kind() == ErrorKind::NoLeftSpaceError => Ok(true),
+            Err(err) => Err(err),
+        }
+    }
+
+    fn fill_to(&mut self, len: usize) -> RpResult<()> {
+        let start = if self.marks > 0 { self.start } else { self.buffer.get_read_pos() };
+        ensure!(self.buffer.get_read_pos() - start + len <= self.max_len,
+                (ErrorKind::BufferOverMaxError, "the message is larger than the max length"));
+        let mut chunk = [0; IO_READ_CHUNK_LEN];
+        while self.buffer.remaining() < len {
+            let read_len = match self.reader.read(&mut chunk) {
+                Ok(read_len) => read_len,
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(From::from(err)),
+            };
+            ensure!(read_len != 0, (ErrorKind::NoLeftSpaceError, "must left space to read "));
+            self.buffer.write_all(&chunk[..read_len])?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> ProtoRead for IoReader<R> {
+    type Mark = usize;
+
+    fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        self.fill_to(buf.len())?;
+        self.buffer.read_fill(buf)
+    }
+
+    fn skip_bytes(&mut self, n: usize) -> RpResult<()> {
+        self.fill_to(n)?;
+        self.buffer.advance(n)
+    }
+
+    fn mark(&mut self) -> usize {
+        // no mark refers to the consumed bytes
+        if self.marks == 0 {
+            if self.buffer.get_read_pos() >= IO_COMPACT_LEN {
+                self.buffer.compact();
+            }
+            self.start = self.buffer.get_read_pos();
+        }
+        self.marks += 1;
+        self.buffer.get_read_pos()
+    }
+
+    fn reset(&mut self, mark: usize) {
+        self.marks -= 1;
+        self.buffer.set_read_pos(mark);
+    }
+
+    fn release(&mut self, _mark: usize) {
+        self.marks -= 1;
+    }
+}
+
+/// encode into any `Write`, every write goes straight to the writer
+pub struct IoWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> IoWriter<W> {
+    pub fn new(writer: W) -> IoWriter<W> {
+        IoWriter {
+            writer,
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// the bytes written to the writer are gone, they can't be taken back
+impl<W: Write> ProtoWrite for IoWriter<W> {
+    type WriteMark = ();
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()> {
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    fn write_mark(&mut self) {}
+
+    fn write_reset(&mut self, _mark: ()) {}
+}
+
+/// the mark is the write position and the length, the bytes overwritten before
+/// the old length are not restored
+impl ProtoWrite for Buffer {
+    type WriteMark = (usize, usize);
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()> {
+        self.write_all(data)?;
+        Ok(())
+    }
+
+    fn write_mark(&mut self) -> (usize, usize) {
+        (self.get_write_pos(), self.len())
+    }
+
+    fn write_reset(&mut self, mark: (usize, usize)) {
+        self.truncate(mark.1);
+        self.set_write_pos(mark.0);
+    }
+}
+
+impl ProtoWrite for RingBuffer {
+    type WriteMark = usize;
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()> {
+        self.write_all(data)?;
+        Ok(())
+    }
+
+    fn write_mark(&mut self) -> usize {
+        self.get_write_pos()
+    }
+
+    fn write_reset(&mut self, mark: usize) {
+        self.set_write_pos(mark)
+    }
+}
+
+impl ProtoWrite for Vec<u8> {
+    type WriteMark = usize;
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn write_mark(&mut self) -> usize {
+        self.len()
+    }
+
+    fn write_reset(&mut self, mark: usize) {
+        self.truncate(mark)
+    }
+}
+
+/// a cursor over a fixed slice fails with an io `WriteZero` error when it is full,
+/// a reset only moves the position back
+impl<T> ProtoWrite for Cursor<T> where Cursor<T>: Write {
+    type WriteMark = u64;
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()> {
+        self.write_all(data)?;
+        Ok(())
+    }
+
+    fn write_mark(&mut self) -> u64 {
+        self.position()
+    }
+
+    fn write_reset(&mut self, mark: u64) {
+        self.set_position(mark)
+    }
+}
+
+#[cfg(feature = "bytes")]
+impl ProtoWrite for BytesMut {
+    type WriteMark = usize;
+
+    fn write_bytes(&mut self, data: &[u8]) -> RpResult<()> {
+        self.extend_from_slice(data);
+        Ok(())
+    }
+
+    fn write_mark(&mut self) -> usize {
+        self.len()
+    }
+
+    fn write_reset(&mut self, mark: usize) {
+        self.truncate(mark)
+    }
+}
@@ -0,0 +1,172 @@
+use std::{cmp, fmt, io};
+use std::io::{Read, Write, Result};
+use crate::error::{ErrorKind, RpError, RpResult};
+use crate::proto_io::Checkpoint;
+
+/// a fixed capacity buffer which reuses the consumed space, the positions keep
+/// growing and wrap into the data so a checkpoint can move the read position back
+pub struct RingBuffer {
+    data: Vec<u8>,
+    read_pos: usize,
+    write_pos: usize,
+}
+
+impl RingBuffer {
+    pub fn new(capacity: usize) -> RingBuffer {
+        RingBuffer {
+            data: vec![0; capacity],
+            read_pos: 0,
+            write_pos: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.data.len()
+    }
+
+    /// the bytes written but not read yet
+    pub fn remaining(&self) -> usize {
+        self.write_pos - self.read_pos
+    }
+
+    /// the bytes which can be written before the buffer is full
+    pub fn free_space(&self) -> usize {
+        self.data.len() - self.remaining()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read_pos == self.write_pos
+    }
+
+    pub fn get_read_pos(&self) -> usize {
+        self.read_pos
+    }
+
+    /// the bytes between the position and the read position must not have been
+    /// overwritten, it holds while nothing is written after the position was taken
+    pub fn set_read_pos(&mut self, r_pos: usize) {
+        assert!(r_pos <= self.write_pos && self.write_pos - r_pos <= self.data.len(),
+                "the read position is out of the ring");
+        self.read_pos = r_pos;
+    }
+
+    pub fn get_write_pos(&self) -> usize {
+        self.write_pos
+    }
+
+    /// take back the bytes written after the position, the unread bytes before
+    /// it are kept
+    pub fn set_write_pos(&mut self, w_pos: usize) {
+        assert!(self.read_pos <= w_pos && w_pos <= self.write_pos, "the write position is out of the unread bytes");
+        self.write_pos = w_pos;
+    }
+
+    pub fn checkpoint(&mut self) -> Checkpoint<'_, RingBuffer> {
+        Checkpoint::new(self)
+    }
+
+    /// the unread bytes, the second slice is not empty if they wrap around the end
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        if self.data.is_empty() {
+            return (&[], &[]);
+        }
+        let start = self.read_pos % self.data.len();
+        let end = start + self.remaining();
+        if end <= self.data.len() {
+            (&self.data[start..end], &[])
+        } else {
+            (&self.data[start..], &self.data[..end - self.data.len()])
+        }
+    }
+
+    /// the next len bytes if they don't wrap around the end, nothing is consumed
+    pub fn peek(&self, len: usize) -> Option<&[u8]> {
+        let (first, _) = self.as_slices();
+        if len <= first.len() {
+            Some(&first[..len])
+        } else {
+            None
+        }
+    }
+
+    /// move the unread bytes to the start of the data so they can be peeked at once,
+    /// the positions move forward so the positions taken before are invalid
+    pub fn make_contiguous(&mut self) -> &[u8] {
+        if self.data.is_empty() {
+            return &[];
+        }
+        let len = self.remaining();
+        let start = self.read_pos % self.data.len();
+        self.data.rotate_left(start);
+        self.read_pos = self.read_pos - start + if start == 0 { 0 } else { self.data.len() };
+        self.write_pos = self.read_pos + len;
+        &self.data[..len]
+    }
+
+    /// skip the next n bytes, nothing is skipped if less are left
+    pub fn advance(&mut self, n: usize) -> RpResult<()> {
+        ensure!(n <= self.remaining(), (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        self.read_pos += n;
+        Ok(())
+    }
+
+    /// fill the whole buf, nothing is consumed if less are left
+    pub fn read_fill(&mut self, buf: &mut [u8]) -> RpResult<()> {
+        ensure!(buf.len() <= self.remaining(), (ErrorKind::NoLeftSpaceError, "must left space to read "));
+        self.consume_into(buf);
+        Ok(())
+    }
+
+    fn consume_into(&mut self, buf: &mut [u8]) {
+        let (first, second) = self.as_slices();
+        let len = cmp::min(first.len(), buf.len());
+        buf[..len].copy_from_slice(&first[..len]);
+        let rest = buf.len() - len;
+        buf[len..].copy_from_slice(&second[..rest]);
+        self.read_pos += buf.len();
+    }
+
+    pub fn clear(&mut self) {
+        self.read_pos = 0;
+        self.write_pos = 0;
+    }
+}
+
+impl fmt::Debug for RingBuffer {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let (first, second) = self.as_slices();
+        write!(fmt, "data is {:?}{:?}", first, second)
+    }
+}
+
+impl Read for RingBuffer {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let read_len = cmp::min(self.remaining(), buf.len());
+        self.consume_into(&mut buf[..read_len]);
+        Ok(read_len)
+    }
+}
+
+impl Write for RingBuffer {
+    /// the whole buf is written or it fails with `BufferOverMaxError` when full
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        if buf.len() > self.free_space() {
+            let err = RpError::from((ErrorKind::BufferOverMaxError, "the buffer over max size"));
+            return Err(io::Error::new(io::ErrorKind::Other, err));
+        }
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        let start = self.write_pos % self.data.len();
+        let len = cmp::min(self.data.len() - start, buf.len());
+        self.data[start..start + len].copy_from_slice(&buf[..len]);
+        self.data[..buf.len() - len].copy_from_slice(&buf[len..]);
+        self.write_pos += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use std::fmt;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{AeadInPlace, KeyInit};
+use zeroize::Zeroize;
+use crate::error::*;
+
+pub const SEAL_KEY_LEN: usize = 32;
+/// the poly1305 tag appended to the sealed payload
+pub const SEAL_TAG_LEN: usize = 16;
+const SEAL_NONCE_LEN: usize = 12;
+
+/// seals and opens the frames of one connection with chacha20-poly1305, the nonce
+/// is a counter per direction which is never sent, so a replayed, dropped or
+/// reordered frame fails to open like a tampered one
+pub struct Sealer {
+    key: [u8; SEAL_KEY_LEN],
+    is_server: bool,
+    send_nonce: u64,
+    recv_nonce: u64,
+}
+
+impl Sealer {
+    /// both peers share the key, `is_server` keeps the two directions from using
+    /// the same nonces
+    pub fn new(key: [u8; SEAL_KEY_LEN], is_server: bool) -> Sealer {
+        Sealer {
+            key,
+            is_server,
+            send_nonce: 0,
+            recv_nonce: 0,
+        }
+    }
+
+    pub fn is_server(&self) -> bool {
+        self.is_server
+    }
+
+    /// the counter of the next sealed frame
+    pub fn get_send_nonce(&self) -> u64 {
+        self.send_nonce
+    }
+
+    /// the counter the next opened frame must be sealed with
+    pub fn get_recv_nonce(&self) -> u64 {
+        self.recv_nonce
+    }
+
+    /// encrypt the data in place and append the tag, the aad is authenticated
+    /// but not encrypted, the nonce is used up even if it fails
+    pub fn seal(&mut self, aad: &[u8], data: &mut Vec<u8>) -> RpResult<()> {
+        ensure!(self.send_nonce != u64::MAX, (ErrorKind::SealError, "the send nonce is used up"));
+        let nonce = make_nonce(self.is_server, self.send_nonce);
+        self.send_nonce += 1;
+        seal_in_place(&self.key, &nonce, aad, data)
+    }
+
+    /// check the tag and decrypt the data in place, the tag is removed
+    pub fn open(&mut self, aad: &[u8], data: &mut Vec<u8>) -> RpResult<()> {
+        ensure!(self.recv_nonce != u64::MAX, (ErrorKind::SealError, "the recv nonce is used up"));
+        let nonce = make_nonce(!self.is_server, self.recv_nonce);
+        open_in_place(&self.key, &nonce, aad, data)?;
+        self.recv_nonce += 1;
+        Ok(())
+    }
+}
+
+/// the key doesn't stay in the freed memory
+impl Drop for Sealer {
+    fn drop(&mut self) {
+        self.key.zeroize();
+    }
+}
+
+/// the key is never printed
+impl fmt::Debug for Sealer {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Sealer {{ is_server: {}, send_nonce: {}, recv_nonce: {} }}",
+               self.is_server, self.send_nonce, self.recv_nonce)
+    }
+}
+
+/// the direction byte and the little endian counter
+fn make_nonce(from_server: bool, counter: u64) -> [u8; SEAL_NONCE_LEN] {
+    let mut nonce = [0; SEAL_NONCE_LEN];
+    nonce[0] = from_server as u8;
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    nonce
+}
+
+fn seal_in_place(key: &[u8; SEAL_KEY_LEN], nonce: &[u8; SEAL_NONCE_LEN], aad: &[u8], data: &mut Vec<u8>) -> RpResult<()> {
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    match cipher.encrypt_in_place(Nonce::from_slice(nonce), aad, data) {
+        Ok(()) => Ok(()),
+        Err(_) => fail!((ErrorKind::SealError, "seal payload failed")),
+    }
+}
+
+fn open_in_place(key: &[u8; SEAL_KEY_LEN], nonce: &[u8; SEAL_NONCE_LEN], aad: &[u8], data: &mut Vec<u8>) -> RpResult<()> {
+    ensure!(data.len() >= SEAL_TAG_LEN, (ErrorKind::SealError, "sealed payload is too short"));
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(key));
+    match cipher.decrypt_in_place(Nonce::from_slice(nonce), aad, data) {
+        Ok(()) => Ok(()),
+        Err(_) => fail!((ErrorKind::SealError, "sealed payload is tampered")),
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::{self, Read, Write};
+use crate::value::*;
+use crate::{Buffer};
+use crate::error::*;
+use crate::config::Config;
+use crate::encode::encode_proto;
+use crate::decode::decode_proto;
+use crate::proto_io::IoReader;
+
+/// write encoded messages to any `Write`, such as a `TcpStream` or a file
+pub struct ProtoWriter<W: Write> {
+    writer: W,
+    buffer: Buffer,
+}
+
+/// read encoded messages from any `Read`, the bytes after a message are kept for the next one
+pub struct ProtoReader<R: Read> {
+    reader: IoReader<R>,
+}
+
+impl<W: Write> ProtoWriter<W> {
+    pub fn new(writer: W) -> ProtoWriter<W> {
+        ProtoWriter {
+            writer,
+            buffer: Buffer::new(),
+        }
+    }
+
+    pub fn write_proto(&mut self, config: &Config, name: &str, infos: Vec<Value>) -> RpResult<()> {
+        self.buffer.clear();
+        encode_proto(&mut self.buffer, config, name, infos)?;
+        self.writer.write_all(self.buffer.get_data())?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> RpResult<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<R: Read> ProtoReader<R> {
+    pub fn new(reader: R) -> ProtoReader<R> {
+        ProtoReader {
+            reader: IoReader::new(reader),
+        }
+    }
+
+    /// read the next message, None if the stream ends between two messages,
+    /// an `UnexpectedEof` io error if it ends in the middle of a message and
+    /// `BufferOverMaxError` if it is larger than the max frame length of the config
+    pub fn read_proto(&mut self, config: &Config) -> RpResult<Option<(String, Vec<Value>)>> {
+        self.reader.set_max_len(config.get_max_frame_len());
+        if self.reader.is_eof()? {
+            return Ok(None);
+        }
+        match decode_proto(&mut self.reader, config) {
+            Ok(result) => Ok(Some(result)),
+            Err(ref err) if err.is_incomplete() => {
+                fail!(io::Error::new(io::ErrorKind::UnexpectedEof, "stream ends in the middle of a message"))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// the bytes read from the stream but not decoded yet
+    pub fn buffered_len(&self) -> usize {
+        self.reader.buffered_len()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.reader.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        self.reader.get_mut()
+    }
+
+    /// the buffered bytes are dropped
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
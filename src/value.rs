@@ -14,6 +14,9 @@ pub const TYPE_F64: u16 = 8;
 pub const TYPE_STR: u16 = 9;
 pub const TYPE_RAW: u16 = 10;
 pub const TYPE_MAP: u16 = 11;
+pub const TYPE_UNION: u16 = 12;
+/// an explicit null value, a `TYPE_NULL` field with index 0 terminates the list
+pub const TYPE_NIL: u16 = 13;
 pub const TYPE_VEC_U8: u16 = 21;
 pub const TYPE_VEC_I8: u16 = 22;
 pub const TYPE_VEC_U16: u16 = 23;
@@ -38,6 +41,8 @@ pub const STR_TYPE_F64: &'static str = "f64";
 pub const STR_TYPE_STR: &'static str = "str";
 pub const STR_TYPE_RAW: &'static str = "raw";
 pub const STR_TYPE_MAP: &'static str = "map";
+pub const STR_TYPE_UNION: &'static str = "union";
+pub const STR_TYPE_NIL: &'static str = "nil";
 pub const STR_TYPE_VEC_U8: &'static str = "u8[]";
 pub const STR_TYPE_VEC_I8: &'static str = "i8[]";
 pub const STR_TYPE_VEC_U16: &'static str = "u16[]";
@@ -75,6 +80,10 @@ pub enum Value {
     VecStr(Vec<Value>),
     VecRaw(Vec<Value>),
     VecMap(Vec<Value>),
+    /// a named enum variant, the numeric value and the variant name
+    Enum(u32, String),
+    /// a tagged union, the arm tag, the arm name and the payload
+    Union(u16, String, Box<Value>),
 }
 
 impl From<u8> for Value {
@@ -261,6 +270,7 @@ pub fn get_vec_elem_type(value: &Value) -> u16{
 
 pub fn get_value_type(value: &Value) -> u16 {
     match *value {
+        Value::Null => TYPE_NIL,
         Value::U8(_) => TYPE_U8,
         Value::I8(_) => TYPE_I8,
         Value::U16(_) => TYPE_U16,
@@ -272,6 +282,7 @@ pub fn get_value_type(value: &Value) -> u16 {
         Value::Str(_) => TYPE_STR,
         Value::Raw(_) => TYPE_RAW,
         Value::Map(_) => TYPE_MAP,
+        Value::Union(_, _, _) => TYPE_UNION,
         Value::VecU8(_) => TYPE_VEC_U8,
         Value::VecI8(_) => TYPE_VEC_I8,
         Value::VecU16(_) => TYPE_VEC_U16,
@@ -301,6 +312,8 @@ pub fn get_type_by_name(name: &str) -> u16 {
         STR_TYPE_STR => TYPE_STR,
         STR_TYPE_RAW => TYPE_RAW,
         STR_TYPE_MAP => TYPE_MAP,
+        STR_TYPE_UNION => TYPE_UNION,
+        STR_TYPE_NIL => TYPE_NIL,
         STR_TYPE_VEC_U8 => TYPE_VEC_U8,
         STR_TYPE_VEC_I8 => TYPE_VEC_I8,
         STR_TYPE_VEC_U16 => TYPE_VEC_U16,
@@ -330,6 +343,8 @@ pub fn get_name_by_type(index: u16) -> &'static str {
         TYPE_STR => STR_TYPE_STR,
         TYPE_RAW => STR_TYPE_RAW,
         TYPE_MAP => STR_TYPE_MAP,
+        TYPE_UNION => STR_TYPE_UNION,
+        TYPE_NIL => STR_TYPE_NIL,
         TYPE_VEC_U8 => STR_TYPE_VEC_U8,
         TYPE_VEC_I8 => STR_TYPE_VEC_I8,
         TYPE_VEC_U16 => STR_TYPE_VEC_U16,
@@ -371,6 +386,8 @@ impl Debug for Value {
             Value::VecStr(ref val) => write!(fmt, "VecStr({:?})", val),
             Value::VecRaw(ref val) => write!(fmt, "VecRaw({:?})", val),
             Value::VecMap(ref val) => write!(fmt, "VecMap({:?})", val),
+            Value::Enum(val, ref name) => write!(fmt, "enum({}({:?}))", name, val),
+            Value::Union(_, ref name, ref val) => write!(fmt, "union({}({:?}))", name, val),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use crate::value::*;
+
+/// a decoded value which borrows the strings and raw bytes from the input
+/// and the names from the config instead of copying them
+#[derive(PartialEq, Clone, Debug)]
+pub enum ValueRef<'a> {
+    Null,
+    U8(u8),
+    I8(i8),
+    U16(u16),
+    I16(i16),
+    U32(u32),
+    I32(i32),
+    F32(f32),
+    F64(f64),
+    Str(&'a str),
+    Raw(&'a [u8]),
+    Map(HashMap<&'a str, ValueRef<'a>>),
+    VecU8(Vec<ValueRef<'a>>),
+    VecI8(Vec<ValueRef<'a>>),
+    VecU16(Vec<ValueRef<'a>>),
+    VecI16(Vec<ValueRef<'a>>),
+    VecU32(Vec<ValueRef<'a>>),
+    VecI32(Vec<ValueRef<'a>>),
+    VecF32(Vec<ValueRef<'a>>),
+    VecF64(Vec<ValueRef<'a>>),
+    VecStr(Vec<ValueRef<'a>>),
+    VecRaw(Vec<ValueRef<'a>>),
+    VecMap(Vec<ValueRef<'a>>),
+    Enum(u32, &'a str),
+    Union(u16, &'a str, Box<ValueRef<'a>>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// copy into an owned `Value`
+    pub fn to_owned(&self) -> Value {
+        match *self {
+            ValueRef::Null => Value::Null,
+            ValueRef::U8(val) => Value::U8(val),
+            ValueRef::I8(val) => Value::I8(val),
+            ValueRef::U16(val) => Value::U16(val),
+            ValueRef::I16(val) => Value::I16(val),
+            ValueRef::U32(val) => Value::U32(val),
+            ValueRef::I32(val) => Value::I32(val),
+            ValueRef::F32(val) => Value::F32(val),
+            ValueRef::F64(val) => Value::F64(val),
+            ValueRef::Str(val) => Value::Str(val.to_string()),
+            ValueRef::Raw(val) => Value::Raw(val.to_vec()),
+            ValueRef::Map(ref val) => {
+                Value::Map(val.iter().map(|(name, v)| (name.to_string(), v.to_owned())).collect())
+            }
+            ValueRef::VecU8(ref val) => Value::VecU8(to_owned_vec(val)),
+            ValueRef::VecI8(ref val) => Value::VecI8(to_owned_vec(val)),
+            ValueRef::VecU16(ref val) => Value::VecU16(to_owned_vec(val)),
+            ValueRef::VecI16(ref val) => Value::VecI16(to_owned_vec(val)),
+            ValueRef::VecU32(ref val) => Value::VecU32(to_owned_vec(val)),
+            ValueRef::VecI32(ref val) => Value::VecI32(to_owned_vec(val)),
+            ValueRef::VecF32(ref val) => Value::VecF32(to_owned_vec(val)),
+            ValueRef::VecF64(ref val) => Value::VecF64(to_owned_vec(val)),
+            ValueRef::VecStr(ref val) => Value::VecStr(to_owned_vec(val)),
+            ValueRef::VecRaw(ref val) => Value::VecRaw(to_owned_vec(val)),
+            ValueRef::VecMap(ref val) => Value::VecMap(to_owned_vec(val)),
+            ValueRef::Enum(val, name) => Value::Enum(val, name.to_string()),
+            ValueRef::Union(tag, name, ref val) => Value::Union(tag, name.to_string(), Box::new((**val).to_owned())),
+        }
+    }
+
+    /// the same type as `get_value_type` of the owned value
+    pub fn get_type(&self) -> u16 {
+        match *self {
+            ValueRef::Null => TYPE_NIL,
+            ValueRef::U8(_) => TYPE_U8,
+            ValueRef::I8(_) => TYPE_I8,
+            ValueRef::U16(_) => TYPE_U16,
+            ValueRef::I16(_) => TYPE_I16,
+            ValueRef::U32(_) => TYPE_U32,
+            ValueRef::I32(_) => TYPE_I32,
+            ValueRef::F32(_) => TYPE_F32,
+            ValueRef::F64(_) => TYPE_F64,
+            ValueRef::Str(_) => TYPE_STR,
+            ValueRef::Raw(_) => TYPE_RAW,
+            ValueRef::Map(_) => TYPE_MAP,
+            ValueRef::Union(_, _, _) => TYPE_UNION,
+            ValueRef::VecU8(_) => TYPE_VEC_U8,
+            ValueRef::VecI8(_) => TYPE_VEC_I8,
+            ValueRef::VecU16(_) => TYPE_VEC_U16,
+            ValueRef::VecI16(_) => TYPE_VEC_I16,
+            ValueRef::VecU32(_) => TYPE_VEC_U32,
+            ValueRef::VecI32(_) => TYPE_VEC_I32,
+            ValueRef::VecF32(_) => TYPE_VEC_F32,
+            ValueRef::VecF64(_) => TYPE_VEC_F64,
+            ValueRef::VecStr(_) => TYPE_VEC_STR,
+            ValueRef::VecRaw(_) => TYPE_VEC_RAW,
+            ValueRef::VecMap(_) => TYPE_VEC_MAP,
+            ValueRef::Enum(_, _) => TYPE_NULL,
+        }
+    }
+}
+
+fn to_owned_vec(val: &Vec<ValueRef>) -> Vec<Value> {
+    val.iter().map(|v| v.to_owned()).collect()
+}
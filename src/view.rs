@@ -0,0 +1,226 @@
+use crate::error::{ErrorKind, RpResult};
+use crate::value::*;
+use crate::value_ref::ValueRef;
+use crate::config::Config;
+use crate::decode_ref::*;
+
+/// a lazy view over an encoded message, only the name is read up front,
+/// the args are located on demand and everything else is skipped in place
+pub struct ProtoView<'a> {
+    data: &'a [u8],
+    config: &'a Config,
+    name: &'a str,
+    args: &'a Vec<String>,
+    args_pos: usize,
+}
+
+/// one encoded field, `data` holds its header and payload
+#[derive(Clone, Debug)]
+pub struct FieldView<'a> {
+    data: &'a [u8],
+    config: &'a Config,
+    value_type: u16,
+    pattern: Option<&'a String>,
+}
+
+pub struct ArgIter<'a> {
+    reader: SliceReader<'a>,
+    config: &'a Config,
+    args: &'a Vec<String>,
+    index: usize,
+    done: bool,
+}
+
+impl<'a> ProtoView<'a> {
+    pub fn new(data: &'a [u8], config: &'a Config) -> RpResult<ProtoView<'a>> {
+        let mut reader = SliceReader::new(data);
+        let name = match decode_str_raw_ref(&mut reader, config, TYPE_STR)? {
+            ValueRef::Str(name) => name,
+            _ => unreachable!("decode str only"),
+        };
+        let proto = unwrap_or!(config.get_proto_by_name(name),
+                               fail!((ErrorKind::TypeNotMatchError, "must match type")));
+        Ok(ProtoView {
+            data,
+            config,
+            name,
+            args: &proto.args,
+            args_pos: reader.get_pos(),
+        })
+    }
+
+    pub fn get_name(&self) -> &'a str {
+        self.name
+    }
+
+    pub fn get_msg_type(&self) -> Option<&'a String> {
+        self.config.get_proto_msg_type(self.name)
+    }
+
+    pub fn args(&self) -> ArgIter<'a> {
+        let mut reader = SliceReader::new(self.data);
+        reader.set_pos(self.args_pos);
+        ArgIter {
+            reader,
+            config: self.config,
+            args: self.args,
+            index: 0,
+            done: false,
+        }
+    }
+
+    /// the arg at `index`, the args before it are skipped
+    pub fn get_arg(&self, index: usize) -> RpResult<Option<FieldView<'a>>> {
+        for (i, arg) in self.args().enumerate() {
+            let arg = arg?;
+            if i == index {
+                return Ok(Some(arg));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<'a> Iterator for ArgIter<'a> {
+    type Item = RpResult<FieldView<'a>>;
+
+    fn next(&mut self) -> Option<RpResult<FieldView<'a>>> {
+        if self.done {
+            return None;
+        }
+        let result = read_field_view(&mut self.reader, self.config, self.args.get(self.index));
+        self.index += 1;
+        match result {
+            Ok(Some(field)) => Some(Ok(field)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<'a> FieldView<'a> {
+    /// the type on the wire
+    pub fn get_type(&self) -> u16 {
+        self.value_type
+    }
+
+    pub fn get_pattern(&self) -> Option<&'a String> {
+        self.pattern
+    }
+
+    /// the encoded bytes of the field, header included
+    pub fn get_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// decode only this field
+    pub fn decode(&self) -> RpResult<ValueRef<'a>> {
+        let mut reader = SliceReader::new(self.data);
+        match self.pattern {
+            Some(pattern) => decode_field_by_pattern_ref(&mut reader, self.config, pattern),
+            None => decode_field_ref(&mut reader, self.config),
+        }
+    }
+
+    /// look up a field of a map by name, the other entries are skipped, None if the
+    /// map doesn't carry it even if the field has a default, `decode` fills that
+    pub fn get_field(&self, name: &str) -> RpResult<Option<FieldView<'a>>> {
+        ensure!(self.value_type == TYPE_MAP,
+                (ErrorKind::TypeNotMatchError, "must match type"));
+        let struct_def = self.pattern.and_then(|pattern| {
+            match self.config.get_nullable_inner(pattern) {
+                Some(inner) => self.config.get_struct_by_name(&inner),
+                None => self.config.get_struct_by_name(pattern),
+            }
+        });
+        let field = match struct_def {
+            Some(def) => def.get_field_by_name(name),
+            None => self.config.get_field_by_name(name),
+        };
+        let field = unwrap_or!(field, return Ok(None));
+
+        let mut reader = SliceReader::new(self.data);
+        read_field_ref(&mut reader)?;
+        loop {
+            let header = read_field_ref(&mut reader)?;
+            if is_terminator(header) {
+                return Ok(None);
+            }
+            if header.0 == field.index {
+                return read_field_view(&mut reader, self.config, Some(&field.pattern));
+            }
+            skip_field(&mut reader, self.config)?;
+        }
+    }
+}
+
+fn read_field_view<'a>(reader: &mut SliceReader<'a>,
+                       config: &'a Config,
+                       pattern: Option<&'a String>)
+                       -> RpResult<Option<FieldView<'a>>> {
+    let start = reader.get_pos();
+    let header = read_field_ref(reader)?;
+    if is_terminator(header) {
+        return Ok(None);
+    }
+    skip_by_type(reader, config, header.1)?;
+    let data = reader.read_span(start);
+    Ok(Some(FieldView {
+        data,
+        config,
+        value_type: header.1,
+        pattern,
+    }))
+}
+
+/// skip an encoded field, header and payload
+pub fn skip_field(reader: &mut SliceReader, config: &Config) -> RpResult<()> {
+    let header = read_field_ref(reader)?;
+    skip_by_type(reader, config, header.1)
+}
+
+fn skip_list(reader: &mut SliceReader, config: &Config) -> RpResult<()> {
+    loop {
+        let header = read_field_ref(reader)?;
+        if is_terminator(header) {
+            return Ok(());
+        }
+        skip_by_type(reader, config, header.1)?;
+    }
+}
+
+fn skip_by_type(reader: &mut SliceReader, config: &Config, value_type: u16) -> RpResult<()> {
+    match value_type {
+        TYPE_U8 | TYPE_I8 => reader.read_bytes(1).map(|_| ()),
+        TYPE_U16 | TYPE_I16 => reader.read_bytes(2).map(|_| ()),
+        TYPE_U32 | TYPE_I32 | TYPE_F32 => reader.read_bytes(4).map(|_| ()),
+        TYPE_F64 => reader.read_bytes(8).map(|_| ()),
+        TYPE_STR | TYPE_RAW => {
+            let len = read_u16(reader)? as usize;
+            reader.read_bytes(len + config.get_padding_len(len)).map(|_| ())
+        }
+        TYPE_MAP => {
+            loop {
+                let header = read_field_ref(reader)?;
+                if is_terminator(header) {
+                    return Ok(());
+                }
+                skip_field(reader, config)?;
+            }
+        }
+        TYPE_UNION => {
+            read_u16(reader)?;
+            skip_field(reader, config)
+        }
+        TYPE_VEC_U8 | TYPE_VEC_I8 | TYPE_VEC_U16 | TYPE_VEC_I16 | TYPE_VEC_U32 | TYPE_VEC_I32 |
+        TYPE_VEC_F32 | TYPE_VEC_F64 | TYPE_VEC_STR | TYPE_VEC_RAW | TYPE_VEC_MAP => skip_list(reader, config),
+        TYPE_NULL | TYPE_NIL => Ok(()),
+        _ => fail!((ErrorKind::TypeNotMatchError, "must match type")),
+    }
+}
@@ -0,0 +1,76 @@
+use std::io::Write;
+use rua_proto::{Buffer, Value, TYPE_STR};
+use rua_proto::config::Config;
+use rua_proto::encode::{encode_proto, encode_str_raw, encoded_proto_len};
+use rua_proto::decode::{decode_proto, decode_str_raw};
+use rua_proto::decode_ref::decode_proto_ref;
+use rua_proto::view::ProtoView;
+
+fn config(align: bool) -> Config {
+    let mut config = Config::new(r#"{"name": {"index": 1, "pattern": "str"}}"#,
+                                 r#"{"cmd": {"msg_type": "server", "args": ["str", "raw", "u16"]}}"#).unwrap();
+    config.set_align(align);
+    config
+}
+
+fn str_bytes(config: &Config, value: &Value) -> Vec<u8> {
+    let mut buffer = Buffer::new();
+    encode_str_raw(&mut buffer, config, value).unwrap();
+    buffer.get_data().clone()
+}
+
+#[test]
+fn test_odd_length_is_padded() {
+    let config = config(true);
+    let value = Value::Str("abc".to_string());
+    assert_eq!(str_bytes(&config, &value), vec![0x03, 0x00, b'a', b'b', b'c', 0x00]);
+    assert_eq!(str_bytes(&config, &Value::Raw(vec![7])), vec![0x01, 0x00, 0x07, 0x00]);
+
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[0x03, 0x00, b'a', b'b', b'c', 0x00, 0xFF]).unwrap();
+    assert_eq!(decode_str_raw(&mut buffer, &config, TYPE_STR).unwrap(), value);
+    assert_eq!(buffer.get_read_pos(), 6);
+}
+
+#[test]
+fn test_even_length_is_not_padded() {
+    let config = config(true);
+    let value = Value::Str("ab".to_string());
+    assert_eq!(str_bytes(&config, &value), vec![0x02, 0x00, b'a', b'b']);
+    assert_eq!(str_bytes(&config, &Value::Str(String::new())), vec![0x00, 0x00]);
+
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[0x02, 0x00, b'a', b'b', 0xFF]).unwrap();
+    assert_eq!(decode_str_raw(&mut buffer, &config, TYPE_STR).unwrap(), value);
+    assert_eq!(buffer.get_read_pos(), 4);
+}
+
+#[test]
+fn test_unaligned_by_default() {
+    let config = config(false);
+    assert!(!config.is_align());
+    assert_eq!(str_bytes(&config, &Value::Str("abc".to_string())), vec![0x03, 0x00, b'a', b'b', b'c']);
+}
+
+#[test]
+fn test_aligned_proto_roundtrip() {
+    for &align in &[true, false] {
+        let config = config(align);
+        let name = "cmd".to_string();
+        let args = vec![Value::Str("odd".to_string()), Value::Raw(vec![1, 2]), Value::U16(9)];
+
+        let mut buffer = Buffer::new();
+        encode_proto(&mut buffer, &config, &name, args.clone()).unwrap();
+        let data = buffer.get_data().clone();
+        assert_eq!(encoded_proto_len(&config, &name, &args).unwrap(), data.len());
+        if align {
+            assert_eq!(data.len() % 2, 0);
+        }
+
+        assert_eq!(decode_proto(&mut buffer, &config).unwrap(), (name.clone(), args.clone()));
+        let (_, args_ref) = decode_proto_ref(&data, &config).unwrap();
+        assert_eq!(args_ref.iter().map(|v| v.to_owned()).collect::<Vec<_>>(), args);
+        let view = ProtoView::new(&data, &config).unwrap();
+        assert_eq!(view.get_arg(2).unwrap().unwrap().decode().unwrap().to_owned(), Value::U16(9));
+    }
+}
@@ -0,0 +1,124 @@
+use std::io::Write;
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::decode_field;
+use rua_proto::encode::{encode_field, encode_proto};
+use rua_proto::error::ErrorKind;
+
+fn config() -> Config {
+    Config::new(r#"{"name": {"index": 1, "pattern": "str"}}"#, r#"{"cmd_say": {"msg_type": "server", "args": ["str"]}}"#).unwrap()
+}
+
+#[test]
+fn test_max_size_write_leaves_buffer_untouched() {
+    let mut buffer = Buffer::with_max_size(8);
+    buffer.write_all(&[1, 2, 3, 4, 5]).unwrap();
+    let err = buffer.write(&[6, 7, 8, 9]).err().unwrap();
+    assert_eq!(err.kind(), std::io::ErrorKind::Other);
+    assert_eq!(buffer.get_data(), &vec![1, 2, 3, 4, 5]);
+    assert_eq!(buffer.get_write_pos(), 5);
+
+    buffer.write_all(&[6, 7, 8]).unwrap();
+    assert_eq!(buffer.len(), 8);
+    assert!(buffer.write(&[9]).is_err());
+}
+
+#[test]
+fn test_max_size_encode_leaves_buffer_untouched() {
+    let config = config();
+    let mut buffer = Buffer::with_max_size(48);
+    encode_proto(&mut buffer, &config, &"cmd_say".to_string(), vec![Value::Str("hi".to_string())]).unwrap();
+    buffer.advance(3).unwrap();
+    let data = buffer.get_data().clone();
+
+    let err = encode_proto(&mut buffer, &config, &"cmd_say".to_string(), vec![Value::Str("a".repeat(32))]).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BufferOverMaxError);
+    assert_eq!(buffer.get_data(), &data);
+    assert_eq!(buffer.get_write_pos(), data.len());
+    assert_eq!(buffer.get_read_pos(), 3);
+
+    encode_proto(&mut buffer, &config, &"cmd_say".to_string(), vec![Value::Str("yo".to_string())]).unwrap();
+    assert_eq!(buffer.len(), data.len() * 2);
+}
+
+#[test]
+fn test_advance_and_read_fill() {
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+    buffer.advance(2).unwrap();
+    assert_eq!(buffer.remaining(), 4);
+    assert_eq!(buffer.advance(5).err().unwrap().kind(), ErrorKind::NoLeftSpaceError);
+    assert_eq!(buffer.get_read_pos(), 2);
+
+    let mut out = [0; 3];
+    buffer.read_fill(&mut out).unwrap();
+    assert_eq!(out, [3, 4, 5]);
+    let mut out = [0; 2];
+    assert_eq!(buffer.read_fill(&mut out).err().unwrap().kind(), ErrorKind::NoLeftSpaceError);
+    assert_eq!(out, [0, 0]);
+    assert_eq!(buffer.get_read_pos(), 5);
+    assert_eq!(buffer.read_bytes(1).unwrap(), &[6]);
+}
+
+#[test]
+fn test_compact() {
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[1, 2, 3, 4]).unwrap();
+    buffer.advance(2).unwrap();
+    buffer.compact();
+    assert_eq!(buffer.len(), 4);
+    assert_eq!(buffer.as_read_slice(), &[3, 4]);
+
+    buffer.advance(2).unwrap();
+    buffer.compact();
+    assert_eq!(buffer.len(), 0);
+    assert_eq!(buffer.get_read_pos(), 0);
+    assert_eq!(buffer.get_write_pos(), 0);
+
+    buffer.write_all(&vec![7; 5000]).unwrap();
+    buffer.write_all(&[8, 9]).unwrap();
+    buffer.advance(5000).unwrap();
+    buffer.compact();
+    assert_eq!(buffer.get_data(), &vec![8, 9]);
+    assert_eq!(buffer.get_read_pos(), 0);
+    assert_eq!(buffer.get_write_pos(), 2);
+    buffer.write_all(&[10]).unwrap();
+    assert_eq!(buffer.as_read_slice(), &[8, 9, 10]);
+}
+
+#[test]
+fn test_checkpoint_rollback_and_commit() {
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[1, 2, 3, 4]).unwrap();
+    {
+        let mut checkpoint = buffer.checkpoint();
+        checkpoint.advance(3).unwrap();
+        assert_eq!(checkpoint.get_read_pos(), 3);
+    }
+    assert_eq!(buffer.get_read_pos(), 0);
+
+    let mut checkpoint = buffer.checkpoint();
+    checkpoint.advance(3).unwrap();
+    checkpoint.commit();
+    assert_eq!(buffer.get_read_pos(), 3);
+}
+
+#[test]
+fn test_failed_decode_restores_read_pos() {
+    let config = config();
+    let mut encoded = Buffer::new();
+    let mut map = std::collections::HashMap::new();
+    map.insert("name".to_string(), Value::Str("value".to_string()));
+    encode_field(&mut encoded, &config, &Value::Map(map.clone())).unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[9]).unwrap();
+    buffer.advance(1).unwrap();
+    buffer.write_all(&encoded.get_data()[..encoded.len() - 1]).unwrap();
+    assert_eq!(decode_field(&mut buffer, &config).err().unwrap().kind(), ErrorKind::NoLeftSpaceError);
+    assert_eq!(buffer.get_read_pos(), 1);
+
+    buffer.write_all(&encoded.get_data()[encoded.len() - 1..]).unwrap();
+    assert_eq!(decode_field(&mut buffer, &config).unwrap(), Value::Map(map));
+    assert_eq!(buffer.remaining(), 0);
+}
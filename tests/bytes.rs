@@ -0,0 +1,57 @@
+#![cfg(feature = "bytes")]
+
+use std::io::Write;
+use bytes::{Buf, BufMut, BytesMut};
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::decode_proto;
+use rua_proto::encode::encode_proto;
+
+fn config() -> Config {
+    Config::new(r#"{}"#, r#"{"cmd_say": {"msg_type": "server", "args": ["str", "u32"]}}"#).unwrap()
+}
+
+#[test]
+fn test_buf_reads_and_advances() {
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[0, 0, 0, 7, 1, 2, 3]).unwrap();
+    assert_eq!(Buf::remaining(&buffer), 7);
+    assert_eq!(buffer.get_u32(), 7);
+    assert_eq!(buffer.chunk(), &[1, 2, 3]);
+    Buf::advance(&mut buffer, 1);
+    assert_eq!(buffer.get_read_pos(), 5);
+
+    let mut out = BytesMut::new();
+    out.put(&mut buffer);
+    assert_eq!(&out[..], &[2, 3]);
+    assert_eq!(Buf::remaining(&buffer), 0);
+}
+
+#[test]
+fn test_buf_mut_writes_decodable_message() {
+    let config = config();
+    let args = vec![Value::Str("hi".to_string()), Value::U32(9)];
+    let mut encoded = Buffer::new();
+    encode_proto(&mut encoded, &config, &"cmd_say".to_string(), args.clone()).unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.put_slice(&encoded.get_data()[..3]);
+    buffer.put_slice(&encoded.get_data()[3..]);
+    assert_eq!(buffer.get_write_pos(), encoded.len());
+    assert_eq!(decode_proto(&mut buffer, &config).unwrap(), ("cmd_say".to_string(), args));
+}
+
+#[test]
+fn test_buf_mut_respects_max_size() {
+    let mut buffer = Buffer::with_max_size(4);
+    assert_eq!(buffer.remaining_mut(), 4);
+    buffer.put_u16(1);
+    assert_eq!(buffer.remaining_mut(), 2);
+    buffer.put_u16(2);
+    assert!(!buffer.has_remaining_mut());
+    assert_eq!(buffer.get_data(), &vec![0, 1, 0, 2]);
+
+    buffer.set_write_pos(1);
+    buffer.put_u8(9);
+    assert_eq!(buffer.get_data(), &vec![0, 9, 0, 2]);
+}
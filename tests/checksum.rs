@@ -0,0 +1,126 @@
+#![cfg(all(feature = "crc32", feature = "xxhash"))]
+
+use std::collections::HashMap;
+use std::io::Write;
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::checksum::Checksum;
+use rua_proto::error::ErrorKind;
+use rua_proto::frame::*;
+
+fn config() -> Config {
+    let field = r#"{"name": {"index": 1, "pattern": "str"}, "hp": {"index": 2, "pattern": "u32"}}"#;
+    let proto = r#"{"cmd_login": {"msg_type": "server", "args": ["str", "map", "u16[]"]}}"#;
+    Config::new(field, proto).unwrap()
+}
+
+fn args() -> Vec<Value> {
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), Value::Str("hero".to_string()));
+    map.insert("hp".to_string(), Value::U32(100));
+    vec![Value::Str("login".to_string()), Value::Map(map), Value::VecU16(vec![Value::U16(1), Value::U16(2)])]
+}
+
+fn session(checksum: Checksum) -> FrameSession {
+    let mut session = FrameSession::new();
+    session.set_checksum(checksum);
+    session
+}
+
+fn encode(checksum: Checksum, seq: Option<u32>) -> Vec<u8> {
+    let mut buffer = Buffer::new();
+    encode_frame_with(&mut buffer, &config(), &mut session(checksum), &"cmd_login".to_string(), args(), seq).unwrap();
+    buffer.get_data().clone()
+}
+
+/// xorshift, so a failure can be replayed
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+#[test]
+fn test_checksum_roundtrip() {
+    for &checksum in &[Checksum::Crc32, Checksum::XxHash32] {
+        let data = encode(checksum, Some(9));
+        let mut buffer = Buffer::new();
+        buffer.write_all(&data).unwrap();
+        let (header, name, infos) = decode_frame_with(&mut buffer, &config(), &mut session(checksum)).unwrap().unwrap();
+        assert!(header.has_flag(checksum.get_flag()));
+        assert_eq!(header.seq, Some(9));
+        assert_eq!(name, "cmd_login");
+        assert_eq!(infos, args());
+    }
+}
+
+#[test]
+fn test_checksum_is_optional_for_the_receiver() {
+    let data = encode(Checksum::Crc32, None);
+    let mut buffer = Buffer::new();
+    buffer.write_all(&data).unwrap();
+    assert_eq!(decode_frame(&mut buffer, &config()).unwrap().unwrap().2, args());
+}
+
+#[test]
+fn test_missing_checksum_rejected() {
+    let data = encode(Checksum::None, None);
+    let mut buffer = Buffer::new();
+    buffer.write_all(&data).unwrap();
+    let err = decode_frame_with(&mut buffer, &config(), &mut session(Checksum::Crc32)).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::ChecksumError);
+}
+
+#[test]
+fn test_flipped_bits_detected() {
+    let mut state = 0x9E3779B97F4A7C15;
+    for &checksum in &[Checksum::Crc32, Checksum::XxHash32] {
+        let data = encode(checksum, Some(1));
+        for _ in 0..2000 {
+            let mut corrupted = data.clone();
+            let bit = next_random(&mut state) as usize % (data.len() * 8);
+            corrupted[bit / 8] ^= 1 << (bit % 8);
+
+            let mut buffer = Buffer::new();
+            buffer.write_all(&corrupted).unwrap();
+            // a flipped length or sequence flag may only make the frame look incomplete
+            // or larger than the max frame length
+            match decode_frame_with(&mut buffer, &config(), &mut session(checksum)) {
+                Ok(None) => {}
+                Ok(Some(_)) => panic!("bit {} flipped without an error", bit),
+                Err(ref err) if err.kind() == ErrorKind::BufferOverMaxError => assert!(bit < 32, "bit {}", bit),
+                Err(err) => assert_eq!(err.kind(), ErrorKind::ChecksumError, "bit {}", bit),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_flipped_payload_bits_detected() {
+    let mut state = 0x2545F4914F6CDD1D;
+    let data = encode(Checksum::Crc32, None);
+    for _ in 0..2000 {
+        let mut corrupted = data.clone();
+        let bit = FRAME_HEADER_LEN * 8 + next_random(&mut state) as usize % ((data.len() - FRAME_HEADER_LEN) * 8);
+        corrupted[bit / 8] ^= 1 << (bit % 8);
+
+        let mut buffer = Buffer::new();
+        buffer.write_all(&corrupted).unwrap();
+        let err = decode_frame_with(&mut buffer, &config(), &mut session(Checksum::Crc32)).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::ChecksumError, "bit {}", bit);
+    }
+}
+
+#[test]
+fn test_both_checksum_flags_rejected() {
+    assert_eq!(Checksum::from_flags(FRAME_FLAG_CRC32 | FRAME_FLAG_XXHASH).err().unwrap().kind(), ErrorKind::ChecksumError);
+    assert_eq!(Checksum::from_flags(FRAME_FLAG_SEQ).unwrap(), Checksum::None);
+
+    let mut data = encode(Checksum::Crc32, None);
+    data[4] |= FRAME_FLAG_XXHASH;
+    let mut buffer = Buffer::new();
+    buffer.write_all(&data).unwrap();
+    let err = decode_frame_with(&mut buffer, &config(), &mut session(Checksum::Crc32)).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::ChecksumError);
+}
@@ -0,0 +1,73 @@
+#![cfg(feature = "tokio")]
+
+use std::sync::Arc;
+use std::collections::HashMap;
+use futures::{SinkExt, StreamExt};
+use tokio_util::codec::{FramedRead, FramedWrite};
+use rua_proto::Value;
+use rua_proto::config::Config;
+use rua_proto::codec::ProtoCodec;
+use rua_proto::error::ErrorKind;
+
+fn config() -> Arc<Config> {
+    let field = r#"{"name": {"index": 1, "pattern": "str"}, "hp": {"index": 2, "pattern": "u32"}}"#;
+    let proto = r#"{"cmd_login": {"msg_type": "server", "args": ["str", "map"]},
+                    "cmd_move": {"msg_type": "server", "args": ["i32", "i32"]}}"#;
+    Arc::new(Config::new(field, proto).unwrap())
+}
+
+fn player() -> Value {
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), Value::Str("hero".to_string()));
+    map.insert("hp".to_string(), Value::U32(100));
+    Value::Map(map)
+}
+
+#[tokio::test]
+async fn test_codec_over_duplex() {
+    let config = config();
+    let (client, server) = tokio::io::duplex(16);
+    let mut writer = FramedWrite::new(client, ProtoCodec::new(config.clone()));
+    let mut reader = FramedRead::new(server, ProtoCodec::new(config.clone()));
+
+    let messages = vec![
+        ("cmd_login".to_string(), vec![Value::Str("token".to_string()), player()]),
+        ("cmd_move".to_string(), vec![Value::I32(-3), Value::I32(7)]),
+    ];
+    let sent = messages.clone();
+    let send = tokio::spawn(async move {
+        for message in sent {
+            writer.send(message).await.unwrap();
+        }
+    });
+
+    for message in messages {
+        assert_eq!(reader.next().await.unwrap().unwrap(), message);
+    }
+    send.await.unwrap();
+    assert!(reader.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_codec_rejects_unknown_proto() {
+    let config = config();
+    let (client, _server) = tokio::io::duplex(64);
+    let mut writer = FramedWrite::new(client, ProtoCodec::new(config));
+    let err = writer.send(("cmd_unknown".to_string(), vec![])).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::MissingError);
+}
+
+#[test]
+fn test_codec_rejects_oversized_header() {
+    use bytes::BytesMut;
+    use tokio_util::codec::Decoder;
+
+    let mut config = Config::new(r#"{}"#, r#"{"cmd_move": {"msg_type": "server", "args": ["i32", "i32"]}}"#).unwrap();
+    config.set_max_frame_len(1024);
+    let mut codec = ProtoCodec::new(Arc::new(config));
+    // a 5 byte header claiming almost 4 GiB
+    let mut src = BytesMut::from(&[0xff, 0xff, 0xff, 0xff, 0][..]);
+    let err = codec.decode(&mut src).unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::BufferOverMaxError);
+    assert!(src.capacity() < 1024);
+}
@@ -0,0 +1,126 @@
+#![cfg(all(feature = "lz4", feature = "zstd"))]
+
+use rua_proto::{Buffer, Value};
+use rua_proto::compress::{Compression, compress};
+use rua_proto::config::Config;
+use rua_proto::error::ErrorKind;
+use rua_proto::frame::*;
+
+fn config() -> Config {
+    Config::new(r#"{}"#, r#"{"cmd_snapshot": {"msg_type": "client", "args": ["str"]}}"#).unwrap()
+}
+
+fn name() -> String {
+    "cmd_snapshot".to_string()
+}
+
+fn args(text: &str, times: usize) -> Vec<Value> {
+    vec![Value::Str(text.repeat(times))]
+}
+
+fn session(compression: Compression, threshold: usize) -> FrameSession {
+    let mut session = FrameSession::new();
+    session.set_compression(compression, threshold);
+    session
+}
+
+fn encode(session: &mut FrameSession, args: Vec<Value>) -> Buffer {
+    let mut buffer = Buffer::new();
+    encode_frame_with(&mut buffer, &config(), session, &name(), args, None).unwrap();
+    buffer
+}
+
+fn decode(session: &mut FrameSession, buffer: &mut Buffer) -> Result<(FrameHeader, Vec<Value>), ErrorKind> {
+    match decode_frame_with(buffer, &config(), session) {
+        Ok(Some((header, _, infos))) => Ok((header, infos)),
+        Ok(None) => Err(ErrorKind::NoLeftSpaceError),
+        Err(err) => Err(err.kind()),
+    }
+}
+
+/// a frame whose flags claim the compression but the payload is replaced
+fn forged(flags: u8, payload: &[u8]) -> Buffer {
+    let mut buffer = Buffer::new();
+    let mut header = FrameHeader::new(payload.len() as u32, None);
+    header.flags |= flags;
+    write_frame_header(&mut buffer, &header).unwrap();
+    std::io::Write::write_all(&mut buffer, payload).unwrap();
+    buffer
+}
+
+#[test]
+fn test_compressed_roundtrip() {
+    for &(compression, flag) in &[(Compression::Lz4, FRAME_FLAG_LZ4), (Compression::Zstd(3), FRAME_FLAG_ZSTD)] {
+        let mut buffer = encode(&mut session(compression, 64), args("abcd", 1000));
+        assert!(buffer.len() < 1000);
+        // the receiver follows the frame flags whatever its own compression is
+        let (header, infos) = decode(&mut FrameSession::new(), &mut buffer).unwrap();
+        assert!(header.has_flag(flag));
+        assert_eq!(infos, args("abcd", 1000));
+    }
+}
+
+#[test]
+fn test_below_threshold_not_compressed() {
+    let mut buffer = encode(&mut session(Compression::Lz4, 1024), args("abcd", 10));
+    let (header, infos) = decode(&mut FrameSession::new(), &mut buffer).unwrap();
+    assert_eq!(header.flags, 0);
+    assert_eq!(infos, args("abcd", 10));
+}
+
+#[test]
+fn test_non_shrinking_payload_sent_as_is() {
+    let mut seed = 0x2545_F491u32;
+    let text: String = (0..200).map(|_| {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        (seed % 94 + 33) as u8 as char
+    }).collect();
+    let mut plain = encode(&mut FrameSession::new(), args(&text, 1));
+    let mut buffer = encode(&mut session(Compression::Lz4, 16), args(&text, 1));
+    assert_eq!(buffer.get_data(), plain.get_data());
+    let (header, infos) = decode(&mut FrameSession::new(), &mut buffer).unwrap();
+    assert_eq!(header.flags, 0);
+    assert_eq!(infos, args(&text, 1));
+    assert_eq!(decode(&mut FrameSession::new(), &mut plain).unwrap().1, infos);
+}
+
+#[test]
+fn test_max_decompress_len_rejects_bomb() {
+    let mut buffer = encode(&mut session(Compression::Zstd(3), 64), args("a", 60_000));
+    assert!(buffer.len() < 1000);
+    let mut receiver = FrameSession::new();
+    receiver.set_max_decompress_len(10_000);
+    assert_eq!(decode(&mut receiver, &mut buffer).err(), Some(ErrorKind::CompressError));
+
+    buffer.set_read_pos(0);
+    receiver.set_max_decompress_len(DEFAULT_MAX_DECOMPRESS_LEN);
+    assert_eq!(decode(&mut receiver, &mut buffer).unwrap().1, args("a", 60_000));
+}
+
+#[test]
+fn test_corrupt_payload_rejected() {
+    for &(compression, flag) in &[(Compression::Lz4, FRAME_FLAG_LZ4), (Compression::Zstd(3), FRAME_FLAG_ZSTD)] {
+        let mut payload = compress(compression, &[7; 512]).unwrap();
+        let len = payload.len();
+        for byte in payload[4..len].iter_mut() {
+            *byte ^= 0x5A;
+        }
+        let mut buffer = forged(flag, &payload);
+        assert_eq!(decode(&mut FrameSession::new(), &mut buffer).err(), Some(ErrorKind::CompressError));
+
+        let mut buffer = forged(flag, &payload[..2]);
+        assert_eq!(decode(&mut FrameSession::new(), &mut buffer).err(), Some(ErrorKind::CompressError));
+    }
+}
+
+#[test]
+fn test_both_flags_rejected() {
+    assert_eq!(Compression::from_flags(FRAME_FLAG_LZ4 | FRAME_FLAG_ZSTD).err().unwrap().kind(), ErrorKind::CompressError);
+    assert_eq!(Compression::from_flags(FRAME_FLAG_SEQ).unwrap(), Compression::None);
+
+    let payload = compress(Compression::Lz4, &[7; 512]).unwrap();
+    let mut buffer = forged(FRAME_FLAG_LZ4 | FRAME_FLAG_ZSTD, &payload);
+    assert_eq!(decode(&mut FrameSession::new(), &mut buffer).err(), Some(ErrorKind::CompressError));
+}
@@ -0,0 +1,127 @@
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::decode_proto;
+use rua_proto::encode::encode_proto;
+use rua_proto::error::ErrorKind;
+
+fn full_config(field: &str, hero: &str) -> Option<Config> {
+    Config::new_from_full_str(&format!(r#"{{
+        "field": {},
+        "proto": {{"cmd_hero": {{"msg_type": "server", "args": ["Hero", "map"]}}}},
+        "enum": {{"Color": {{"pattern": "u8", "variants": {{"Red": 1, "Blue": 2}}}}}},
+        "struct": {{"Hero": {{"fields": {}}}}}
+    }}"#, field, hero))
+}
+
+fn config() -> Config {
+    full_config(r#"{"hp": {"index": 1, "pattern": "u32"}, "name": {"index": 2, "pattern": "str"}}"#,
+                r#"{"id": {"index": 1, "pattern": "u32", "required": true},
+                    "lv": {"index": 2, "pattern": "u16", "default": "1"},
+                    "title": {"index": 3, "pattern": "str", "default": "nobody"},
+                    "color": {"index": 4, "pattern": "Color", "default": "Blue"},
+                    "mp": {"index": 5, "pattern": "i32?", "default": "-5"},
+                    "exp": {"index": 6, "pattern": "u32"}}"#).unwrap()
+}
+
+fn map(values: Vec<(&str, Value)>) -> Value {
+    Value::Map(values.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+}
+
+fn roundtrip(config: &Config, args: Vec<Value>) -> Vec<Value> {
+    let mut buffer = Buffer::new();
+    encode_proto(&mut buffer, config, &"cmd_hero".to_string(), args).unwrap();
+    decode_proto(&mut buffer, config).unwrap().1
+}
+
+#[test]
+fn test_defaults_filled() {
+    let config = config();
+    let value = roundtrip(&config, vec![map(vec![("id", Value::U32(7))]), map(vec![])]);
+    assert_eq!(value[0], map(vec![("id", Value::U32(7)),
+                                  ("lv", Value::U16(1)),
+                                  ("title", Value::Str("nobody".to_string())),
+                                  ("color", Value::Enum(2, "Blue".to_string())),
+                                  ("mp", Value::I32(-5))]));
+    // an untyped map never gets the struct defaults
+    assert_eq!(value[1], map(vec![]));
+}
+
+#[test]
+fn test_present_values_kept() {
+    let config = config();
+    let hero = map(vec![("id", Value::U32(7)),
+                        ("lv", Value::U16(9)),
+                        ("title", Value::Str("king".to_string())),
+                        ("color", Value::Enum(1, "Red".to_string())),
+                        ("mp", Value::Null),
+                        ("exp", Value::U32(3))]);
+    let value = roundtrip(&config, vec![hero.clone(), map(vec![("hp", Value::U32(1))])]);
+    assert_eq!(value[0], hero);
+}
+
+#[test]
+fn test_omitted_defaults_filled_back() {
+    let mut config = config();
+    config.set_omit_default(true);
+    let hero = map(vec![("id", Value::U32(7)),
+                        ("lv", Value::U16(1)),
+                        ("title", Value::Str("nobody".to_string())),
+                        ("color", Value::Enum(2, "Blue".to_string())),
+                        ("mp", Value::I32(-5))]);
+
+    let mut omitted = Buffer::new();
+    encode_proto(&mut omitted, &config, &"cmd_hero".to_string(), vec![hero.clone(), map(vec![])]).unwrap();
+    let mut full = Buffer::new();
+    encode_proto(&mut full, &self::config(), &"cmd_hero".to_string(), vec![hero.clone(), map(vec![])]).unwrap();
+    assert!(omitted.get_data().len() < full.get_data().len());
+    assert_eq!(decode_proto(&mut omitted, &config).unwrap().1[0], hero);
+}
+
+#[test]
+fn test_missing_required_rejected() {
+    let config = config();
+    let mut buffer = Buffer::new();
+    let err = encode_proto(&mut buffer, &config, &"cmd_hero".to_string(),
+                           vec![map(vec![("lv", Value::U16(2))]), map(vec![])]).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MissingError);
+    assert_eq!(format!("{}", err), "missing required fields: id");
+
+    // a peer whose Hero has no id field
+    let other = full_config(r#"{}"#, r#"{"other": {"index": 9, "pattern": "u32", "required": true}}"#).unwrap();
+    let mut buffer = Buffer::new();
+    encode_proto(&mut buffer, &other, &"cmd_hero".to_string(),
+                 vec![map(vec![("other", Value::U32(1))]), map(vec![])]).unwrap();
+    let err = decode_proto(&mut buffer, &config).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MissingError);
+    assert_eq!(format!("{}", err), "missing required fields: id");
+    assert_eq!(buffer.get_read_pos(), 0);
+}
+
+#[test]
+fn test_global_required_and_default_rejected() {
+    let hero = r#"{"id": {"index": 1, "pattern": "u32"}}"#;
+    assert!(full_config(r#"{"hp": {"index": 1, "pattern": "u32"}}"#, hero).is_some());
+    assert!(full_config(r#"{"hp": {"index": 1, "pattern": "u32", "required": true}}"#, hero).is_none());
+    assert!(full_config(r#"{"hp": {"index": 1, "pattern": "u32", "default": "3"}}"#, hero).is_none());
+
+    let proto = r#"{"cmd_hero": {"msg_type": "server", "args": ["map"]}}"#;
+    assert!(Config::new(r#"{"hp": {"index": 1, "pattern": "u32"}}"#, proto).is_some());
+    assert!(Config::new(r#"{"hp": {"index": 1, "pattern": "u32", "required": false}}"#, proto).is_none());
+    assert!(Config::new(r#"{"hp": {"index": 1, "pattern": "u32", "default": "3"}}"#, proto).is_none());
+}
+
+#[test]
+fn test_bad_default_rejected() {
+    let fields = r#"{}"#;
+    for hero in &[r#"{"id": {"index": 1, "pattern": "u32", "default": "abc"}}"#,
+                  r#"{"id": {"index": 1, "pattern": "u8", "default": "256"}}"#,
+                  r#"{"id": {"index": 1, "pattern": "i16?", "default": "1.5"}}"#,
+                  r#"{"id": {"index": 1, "pattern": "Color", "default": "Green"}}"#,
+                  r#"{"id": {"index": 1, "pattern": "Hero", "default": "x"}}"#,
+                  r#"{"id": {"index": 1, "pattern": "u32[]", "default": "1"}}"#] {
+        assert!(full_config(fields, hero).is_none(), "{}", hero);
+    }
+    assert!(full_config(fields, r#"{"id": {"index": 1, "pattern": "f32", "default": "1.5"}}"#).is_some());
+    assert!(full_config(fields, r#"{"id": {"index": 1, "pattern": "Color?", "default": "Red"}}"#).is_some());
+    assert!(full_config(fields, r#"{"id": {"index": 1, "pattern": "raw", "default": "abc"}}"#).is_some());
+}
@@ -0,0 +1,79 @@
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::encode::{encode_proto, encoded_proto_len};
+
+fn config(args: &str, align: bool, omit_default: bool) -> Config {
+    let mut config = Config::new_from_full_str(&format!(r#"{{
+        "field": {{"hp": {{"index": 1, "pattern": "u32"}}, "name": {{"index": 2, "pattern": "str"}}, "mp": {{"index": 3, "pattern": "u16?"}}}},
+        "proto": {{"cmd_test": {{"msg_type": "server", "args": {}}}}},
+        "enum": {{"Color": {{"pattern": "u8", "variants": {{"Red": 1, "Blue": 2}}}}}},
+        "union": {{"Effect": {{"arms": {{"heal": {{"tag": 1, "pattern": "u32"}}, "buff": {{"tag": 2, "pattern": "Hero"}}, "maybe": {{"tag": 3, "pattern": "str?"}}}}}}}},
+        "struct": {{"Hero": {{"fields": {{"id": {{"index": 1, "pattern": "u32"}},
+                                          "lv": {{"index": 2, "pattern": "u16", "default": "1"}},
+                                          "title": {{"index": 3, "pattern": "str", "default": "nobody"}},
+                                          "color": {{"index": 4, "pattern": "Color", "default": "Blue"}},
+                                          "mp": {{"index": 5, "pattern": "i32?"}}}}}}}}
+    }}"#, args)).unwrap();
+    config.set_align(align);
+    config.set_omit_default(omit_default);
+    config
+}
+
+fn map(values: Vec<(&str, Value)>) -> Value {
+    Value::Map(values.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+}
+
+fn hero(id: u32, title: &str) -> Value {
+    map(vec![("id", Value::U32(id)),
+             ("lv", Value::U16(1)),
+             ("title", Value::Str(title.to_string())),
+             ("color", Value::Enum(1, "Red".to_string())),
+             ("mp", Value::Null)])
+}
+
+/// the predicted length must match the encoded one for every config variant
+fn check(args: &str, values: Vec<Value>) {
+    for &(align, omit_default) in &[(false, false), (true, false), (false, true), (true, true)] {
+        let config = config(args, align, omit_default);
+        let name = "cmd_test".to_string();
+        let mut buffer = Buffer::new();
+        encode_proto(&mut buffer, &config, &name, values.clone()).unwrap();
+        assert_eq!(encoded_proto_len(&config, &name, &values).unwrap(), buffer.len(),
+                   "align {} omit_default {} args {}", align, omit_default, args);
+    }
+}
+
+#[test]
+fn test_builtin_len() {
+    check(r#"["u8", "i32", "f32", "str", "raw", "u16[]", "str[]", "map"]"#,
+          vec![Value::U8(1), Value::I32(-7), Value::F32(1.5), Value::Str("odd".to_string()),
+               Value::Raw(vec![1, 2, 3]), Value::VecU16(vec![Value::U16(1), Value::U16(2)]),
+               Value::VecStr(vec![Value::Str("a".to_string()), Value::Str("bc".to_string())]),
+               map(vec![("hp", Value::U32(3)), ("name", Value::Str("abc".to_string())), ("mp", Value::Null)])]);
+}
+
+#[test]
+fn test_enum_and_union_len() {
+    check(r#"["Color", "Effect", "Effect", "Effect", "Effect"]"#,
+          vec![Value::Enum(2, "Blue".to_string()),
+               Value::Union(1, "heal".to_string(), Box::new(Value::U32(5))),
+               Value::Union(2, "buff".to_string(), Box::new(hero(1, "abc"))),
+               Value::Union(3, "maybe".to_string(), Box::new(Value::Str("x".to_string()))),
+               Value::Union(3, "maybe".to_string(), Box::new(Value::Null))]);
+}
+
+#[test]
+fn test_struct_len() {
+    check(r#"["Hero", "Hero", "Hero[]"]"#,
+          vec![hero(1, "nobody"), hero(2, "odd"), Value::VecMap(vec![hero(3, "a"), hero(4, "nobody")])]);
+    check(r#"["Hero[]"]"#, vec![Value::VecMap(vec![])]);
+}
+
+#[test]
+fn test_nullable_len() {
+    check(r#"["u32?", "u32?", "Hero?", "Hero?[]", "u8?[]", "str?[]"]"#,
+          vec![Value::U32(1), Value::Null, Value::Null,
+               Value::VecMap(vec![hero(1, "abc"), Value::Null]),
+               Value::VecU8(vec![Value::U8(1), Value::Null]),
+               Value::VecStr(vec![Value::Null, Value::Str("abc".to_string())])]);
+}
@@ -0,0 +1,137 @@
+use std::io::{self, Read, Write};
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::error::ErrorKind;
+use rua_proto::frame::*;
+use rua_proto::proto_io::IoReader;
+use rua_proto::stream::ProtoReader;
+use rua_proto::encode::encode_proto;
+
+fn config(max_frame_len: usize) -> Config {
+    let proto = r#"{"cmd_say": {"msg_type": "server", "args": ["str"]}}"#;
+    let mut config = Config::new(r#"{}"#, proto).unwrap();
+    config.set_max_frame_len(max_frame_len);
+    config
+}
+
+fn say(len: usize) -> Vec<Value> {
+    vec![Value::Str("a".repeat(len))]
+}
+
+/// a 5 byte header which claims almost 4 GiB
+const OVERSIZED: [u8; 5] = [0xff, 0xff, 0xff, 0xff, 0];
+
+/// fails the test if the payload of the oversized frame is read
+struct HeaderOnly(io::Cursor<Vec<u8>>);
+
+impl Read for HeaderOnly {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        assert!(self.0.position() < self.0.get_ref().len() as u64, "read past the header");
+        self.0.read(buf)
+    }
+}
+
+#[test]
+fn test_oversized_header_rejected() {
+    let config = config(1024);
+    let err = check_frame_bytes(&OVERSIZED, &config).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BufferOverMaxError);
+
+    let mut buffer = Buffer::new();
+    buffer.write_all(&OVERSIZED).unwrap();
+    assert_eq!(check_frame(&buffer, &config).err().unwrap().kind(), ErrorKind::BufferOverMaxError);
+    assert_eq!(decode_frame(&mut buffer, &config).err().unwrap().kind(), ErrorKind::BufferOverMaxError);
+    assert_eq!(buffer.get_read_pos(), 0);
+
+    let mut reader = IoReader::new(HeaderOnly(io::Cursor::new(OVERSIZED.to_vec())));
+    assert_eq!(read_frame(&mut reader, &config).err().unwrap().kind(), ErrorKind::BufferOverMaxError);
+}
+
+#[test]
+fn test_frame_at_max_len_accepted() {
+    let mut buffer = Buffer::new();
+    encode_frame(&mut buffer, &config(1024), &"cmd_say".to_string(), say(100), None).unwrap();
+    let length = buffer.get_data().len() - FRAME_HEADER_LEN;
+
+    let mut exact = Buffer::new();
+    exact.write_all(buffer.get_data()).unwrap();
+    assert_eq!(decode_frame(&mut exact, &config(length)).unwrap().unwrap().2, say(100));
+    assert_eq!(decode_frame(&mut buffer, &config(length - 1)).err().unwrap().kind(), ErrorKind::BufferOverMaxError);
+}
+
+#[test]
+fn test_stream_message_over_max_len_rejected() {
+    let mut data = vec![];
+    encode_proto(&mut data, &config(1024), &"cmd_say".to_string(), say(10)).unwrap();
+    encode_proto(&mut data, &config(1024), &"cmd_say".to_string(), say(2000)).unwrap();
+
+    let mut reader = ProtoReader::new(io::Cursor::new(data));
+    assert_eq!(reader.read_proto(&config(1024)).unwrap().unwrap().1, say(10));
+    assert_eq!(reader.read_proto(&config(1024)).err().unwrap().kind(), ErrorKind::BufferOverMaxError);
+}
+
+#[test]
+fn test_sealed_frame_rejected_without_key() {
+    let mut payload = Vec::new();
+    encode_proto(&mut payload, &config(1024), &"cmd_say".to_string(), say(4)).unwrap();
+    payload.extend_from_slice(&[0; 16]);
+    let mut header = FrameHeader::new(payload.len() as u32, None);
+    header.flags |= FRAME_FLAG_SEALED;
+    let mut buffer = Buffer::new();
+    write_frame_header(&mut buffer, &header).unwrap();
+    buffer.write_all(&payload).unwrap();
+
+    let err = decode_frame_with(&mut buffer, &config(1024), &mut FrameSession::new()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::SealError);
+}
+
+#[test]
+fn test_rejected_value_leaves_no_partial_frame() {
+    let config = Config::new_from_full_str(r#"{
+        "field": {},
+        "proto": {"cmd_hero": {"msg_type": "server", "args": ["str", "Hero"]}},
+        "struct": {"Hero": {"fields": {"id": {"index": 1, "pattern": "u32", "required": true},
+                                       "name": {"index": 2, "pattern": "str"}}}}
+    }"#).unwrap();
+    let mut hero = std::collections::HashMap::new();
+    hero.insert("name".to_string(), Value::Str("hero".to_string()));
+    let args = vec![Value::Str("join".to_string()), Value::Map(hero)];
+
+    let mut buffer = Buffer::new();
+    let err = encode_frame(&mut buffer, &config, &"cmd_hero".to_string(), args, Some(1)).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::MissingError);
+    assert_eq!(buffer.len(), 0);
+}
+
+#[test]
+fn test_check_frame_byte_by_byte() {
+    let config = config(1024);
+    let mut encoded = Buffer::new();
+    encode_frame(&mut encoded, &config, &"cmd_say".to_string(), say(3), Some(9)).unwrap();
+    let data = encoded.get_data().clone();
+
+    let mut buffer = Buffer::new();
+    buffer.write_all(&[0xAA]).unwrap();
+    buffer.advance(1).unwrap();
+    for (i, byte) in data.iter().enumerate() {
+        let expect = if i < FRAME_HEADER_LEN {
+            FrameState::NeedMore(FRAME_HEADER_LEN - i)
+        } else if i < FRAME_HEADER_LEN + FRAME_SEQ_LEN {
+            FrameState::NeedMore(FRAME_HEADER_LEN + FRAME_SEQ_LEN - i)
+        } else {
+            FrameState::NeedMore(data.len() - i)
+        };
+        assert_eq!(check_frame(&buffer, &config).unwrap(), expect);
+        assert_eq!(read_frame(&mut buffer, &config).unwrap(), None);
+        assert_eq!(buffer.get_read_pos(), 1);
+        buffer.write_all(&[*byte]).unwrap();
+    }
+
+    let header = FrameHeader::new((data.len() - FRAME_HEADER_LEN - FRAME_SEQ_LEN) as u32, Some(9));
+    assert_eq!(check_frame(&buffer, &config).unwrap(), FrameState::Ready(header.clone()));
+    assert_eq!(buffer.get_read_pos(), 1);
+    let (read, payload) = read_frame(&mut buffer, &config).unwrap().unwrap();
+    assert_eq!(read, header);
+    assert_eq!(payload.len(), header.length as usize);
+    assert_eq!(buffer.get_read_pos(), 1 + data.len());
+}
@@ -0,0 +1,71 @@
+use std::io::Write;
+use rua_proto::{Buffer, Value, ValueRef, get_value_type};
+use rua_proto::config::Config;
+use rua_proto::encode::{encode_number, encode_proto};
+use rua_proto::decode::{decode_number, decode_proto};
+use rua_proto::decode_ref::{SliceReader, decode_number_ref, decode_proto_ref};
+
+/// the wire is little endian whatever the host byte order is
+fn number_vectors() -> Vec<(Value, Vec<u8>)> {
+    vec![
+        (Value::U8(0xAB), vec![0xAB]),
+        (Value::I8(-2), vec![0xFE]),
+        (Value::U16(0x1234), vec![0x34, 0x12]),
+        (Value::I16(-2), vec![0xFE, 0xFF]),
+        (Value::U32(0x12345678), vec![0x78, 0x56, 0x34, 0x12]),
+        (Value::I32(-100000), vec![0x60, 0x79, 0xFE, 0xFF]),
+        // floats travel as fixed point with three decimals
+        (Value::F32(1.5), vec![0xDC, 0x05, 0x00, 0x00]),
+        (Value::F32(-0.25), vec![0x06, 0xFF, 0xFF, 0xFF]),
+        (Value::F64(1.5), vec![0xDC, 0x05, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]),
+        (Value::F64(-0.25), vec![0x06, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]),
+    ]
+}
+
+#[test]
+fn test_encode_number_golden() {
+    for (value, bytes) in number_vectors() {
+        let mut buffer = Buffer::new();
+        encode_number(&mut buffer, &value).unwrap();
+        assert_eq!(buffer.get_data(), &bytes, "{:?}", value);
+    }
+}
+
+#[test]
+fn test_decode_number_golden() {
+    for (value, bytes) in number_vectors() {
+        let mut buffer = Buffer::new();
+        buffer.write_all(&bytes).unwrap();
+        assert_eq!(decode_number(&mut buffer, get_value_type(&value)).unwrap(), value);
+
+        let mut reader = SliceReader::new(&bytes);
+        let value_ref = decode_number_ref(&mut reader, get_value_type(&value)).unwrap();
+        assert_eq!(ValueRef::to_owned(&value_ref), value);
+    }
+}
+
+#[test]
+fn test_proto_golden() {
+    let config = Config::new(r#"{"hp": {"index": 1, "pattern": "u16"}}"#,
+                             r#"{"cmd": {"msg_type": "server", "args": ["u32", "str"]}}"#).unwrap();
+    let bytes = vec![
+        // the proto name
+        0x03, 0x00, b'c', b'm', b'd',
+        // u32 arg
+        0x00, 0x00, 0x05, 0x00, 0x04, 0x03, 0x02, 0x01,
+        // str arg
+        0x00, 0x00, 0x09, 0x00, 0x02, 0x00, b'o', b'k',
+        // the terminator
+        0x00, 0x00, 0x00, 0x00,
+    ];
+    let args = vec![Value::U32(0x01020304), Value::Str("ok".to_string())];
+
+    let mut buffer = Buffer::new();
+    encode_proto(&mut buffer, &config, &"cmd".to_string(), args.clone()).unwrap();
+    assert_eq!(buffer.get_data(), &bytes);
+
+    assert_eq!(decode_proto(&mut buffer, &config).unwrap(), ("cmd".to_string(), args.clone()));
+    let (name, args_ref) = decode_proto_ref(&bytes, &config).unwrap();
+    assert_eq!(name, "cmd");
+    assert_eq!(args_ref.iter().map(ValueRef::to_owned).collect::<Vec<_>>(), args);
+}
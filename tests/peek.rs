@@ -0,0 +1,42 @@
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::{decode_proto, peek_msg_type, peek_proto_name};
+use rua_proto::encode::encode_proto;
+use rua_proto::error::ErrorKind;
+
+fn config() -> Config {
+    Config::new(r#"{}"#, r#"{"cmd_say": {"msg_type": "server", "args": ["str"]}}"#).unwrap()
+}
+
+#[test]
+fn test_peek_does_not_advance() {
+    let config = config();
+    let mut buffer = Buffer::new();
+    encode_proto(&mut buffer, &config, &"cmd_say".to_string(), vec![Value::Str("hi".to_string())]).unwrap();
+    let len = buffer.len();
+
+    assert_eq!(peek_proto_name(&buffer).unwrap(), "cmd_say");
+    assert_eq!(peek_msg_type(&buffer, &config).unwrap(), Some(&"server".to_string()));
+    assert_eq!(peek_proto_name(&buffer).unwrap(), "cmd_say");
+    assert_eq!(buffer.get_read_pos(), 0);
+    assert_eq!(buffer.len(), len);
+
+    let (name, args) = decode_proto(&mut buffer, &config).unwrap();
+    assert_eq!(name, "cmd_say");
+    assert_eq!(args, vec![Value::Str("hi".to_string())]);
+}
+
+#[test]
+fn test_peek_unknown_and_incomplete() {
+    let config = config();
+    let mut buffer = Buffer::new();
+    encode_proto(&mut buffer, &Config::new(r#"{}"#, r#"{"cmd_other": {"msg_type": "client", "args": []}}"#).unwrap(),
+                 &"cmd_other".to_string(), vec![]).unwrap();
+    assert_eq!(peek_msg_type(&buffer, &config).unwrap(), None);
+    assert_eq!(buffer.get_read_pos(), 0);
+
+    let mut partial = Buffer::new();
+    std::io::Write::write_all(&mut partial, &buffer.get_data()[..3]).unwrap();
+    assert_eq!(peek_proto_name(&partial).err().unwrap().kind(), ErrorKind::IncompleteError);
+    assert_eq!(partial.get_read_pos(), 0);
+}
@@ -0,0 +1,47 @@
+use std::io::Write;
+use rua_proto::pool::{BufferPool, PoolStats};
+
+#[test]
+fn test_pool_reuses_cleared_buffers() {
+    let pool = BufferPool::new(2, 1024);
+    let mut buffer = pool.get();
+    buffer.write_all(&[1, 2, 3]).unwrap();
+    buffer.set_max_size(8);
+    let capacity = buffer.capacity();
+    drop(buffer);
+
+    let buffer = pool.get();
+    assert_eq!(buffer.len(), 0);
+    assert_eq!(buffer.get_read_pos(), 0);
+    assert_eq!(buffer.get_write_pos(), 0);
+    assert_eq!(buffer.get_max_size(), usize::MAX);
+    assert_eq!(buffer.capacity(), capacity);
+    assert_eq!(pool.stats(), PoolStats { hits: 1, misses: 1, pooled: 0, bytes_retained: 0 });
+}
+
+#[test]
+fn test_pool_stats_and_limits() {
+    let pool = BufferPool::new(2, 64);
+    let clone = pool.clone();
+    let mut a = pool.get();
+    let mut b = clone.get();
+    let mut c = pool.get();
+    a.write_all(&[0; 16]).unwrap();
+    b.write_all(&[0; 16]).unwrap();
+    c.write_all(&[0; 16]).unwrap();
+    let retained = a.capacity() + b.capacity();
+    drop(a);
+    drop(b);
+    drop(c);
+    assert_eq!(pool.stats(), PoolStats { hits: 0, misses: 3, pooled: 2, bytes_retained: retained });
+
+    let mut big = pool.get();
+    big.write_all(&[0; 128]).unwrap();
+    drop(big);
+    let stats = clone.stats();
+    assert_eq!((stats.hits, stats.misses, stats.pooled), (1, 3, 1));
+
+    let owned = pool.get().into_inner();
+    assert_eq!(owned.len(), 0);
+    assert_eq!(pool.stats().pooled, 0);
+}
@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::decode_proto;
+use rua_proto::encode::encode_proto;
+use rua_proto::frame::{encode_frame, decode_frame, read_frame};
+use rua_proto::proto_io::{IoReader, IoWriter, ProtoRead};
+
+fn config() -> Config {
+    let field = r#"{"name": {"index": 1, "pattern": "str"}, "hp": {"index": 2, "pattern": "u32"}}"#;
+    let proto = r#"{"cmd_login": {"msg_type": "server", "args": ["str", "map", "u16[]"]}}"#;
+    Config::new(field, proto).unwrap()
+}
+
+/// one map entry, so the bytes don't depend on the map order
+fn args(hp: u32) -> Vec<Value> {
+    let mut map = HashMap::new();
+    map.insert("hp".to_string(), Value::U32(hp));
+    vec![Value::Str("login".to_string()), Value::Map(map), Value::VecU16(vec![Value::U16(1), Value::U16(2)])]
+}
+
+fn name() -> String {
+    "cmd_login".to_string()
+}
+
+/// two messages written through a `Vec`
+fn encode_two() -> Vec<u8> {
+    let mut data = vec![];
+    encode_proto(&mut data, &config(), &name(), args(1)).unwrap();
+    encode_proto(&mut data, &config(), &name(), args(2)).unwrap();
+    data
+}
+
+#[test]
+fn test_slice_roundtrip() {
+    let data = encode_two();
+    let mut slice = &data[..];
+    assert_eq!(decode_proto(&mut slice, &config()).unwrap(), (name(), args(1)));
+    assert_eq!(decode_proto(&mut slice, &config()).unwrap(), (name(), args(2)));
+    assert!(slice.is_empty());
+
+    // an incomplete message leaves the slice alone
+    let mut slice = &data[..data.len() / 2 + 3];
+    decode_proto(&mut slice, &config()).unwrap();
+    let rest = slice;
+    assert!(decode_proto(&mut slice, &config()).err().unwrap().is_incomplete());
+    assert_eq!(slice, rest);
+}
+
+#[test]
+fn test_cursor_roundtrip() {
+    let mut cursor = Cursor::new(vec![0; 3]);
+    cursor.set_position(3);
+    encode_proto(&mut cursor, &config(), &name(), args(1)).unwrap();
+    encode_proto(&mut cursor, &config(), &name(), args(2)).unwrap();
+    assert_eq!(&cursor.get_ref()[3..], &encode_two()[..]);
+
+    cursor.set_position(3);
+    assert_eq!(decode_proto(&mut cursor, &config()).unwrap(), (name(), args(1)));
+    assert_eq!(decode_proto(&mut cursor, &config()).unwrap(), (name(), args(2)));
+    let end = cursor.position();
+    assert!(decode_proto(&mut cursor, &config()).err().unwrap().is_incomplete());
+    assert_eq!(cursor.position(), end);
+}
+
+#[test]
+fn test_io_adaptors_roundtrip() {
+    let mut writer = IoWriter::new(vec![]);
+    encode_proto(&mut writer, &config(), &name(), args(1)).unwrap();
+    encode_proto(&mut writer, &config(), &name(), args(2)).unwrap();
+    let data = writer.into_inner();
+    assert_eq!(data, encode_two());
+
+    // one byte per read
+    let mut reader = IoReader::new(OneByte(Cursor::new(data)));
+    assert_eq!(decode_proto(&mut reader, &config()).unwrap(), (name(), args(1)));
+    assert_eq!(decode_proto(&mut reader, &config()).unwrap(), (name(), args(2)));
+    assert!(reader.is_eof().unwrap());
+    assert!(decode_proto(&mut reader, &config()).err().unwrap().is_incomplete());
+}
+
+#[test]
+fn test_io_reader_keeps_bytes_of_failed_decode() {
+    let data = encode_two();
+    let mut reader = IoReader::new(Cursor::new(data[..data.len() - 1].to_vec()));
+    decode_proto(&mut reader, &config()).unwrap();
+    let len = reader.buffered_len();
+    assert!(decode_proto(&mut reader, &config()).err().unwrap().is_incomplete());
+    assert_eq!(reader.buffered_len(), len);
+    let mut rest = vec![0; len];
+    reader.read_fill(&mut rest).unwrap();
+    assert_eq!(&rest[..], &data[data.len() - 1 - len..data.len() - 1]);
+}
+
+#[test]
+fn test_frames_through_readers() {
+    let mut buffer = Buffer::new();
+    encode_frame(&mut buffer, &config(), &name(), args(1), Some(1)).unwrap();
+    encode_frame(&mut buffer, &config(), &name(), args(2), None).unwrap();
+    let data = buffer.get_data().clone();
+
+    let mut slice = &data[..];
+    assert_eq!(decode_frame(&mut slice, &config()).unwrap().unwrap().2, args(1));
+    assert_eq!(decode_frame(&mut slice, &config()).unwrap().unwrap().2, args(2));
+    assert!(decode_frame(&mut slice, &config()).unwrap().is_none());
+
+    let mut cursor = Cursor::new(&data[..data.len() - 1]);
+    let (header, _) = read_frame(&mut cursor, &config()).unwrap().unwrap();
+    assert_eq!(header.seq, Some(1));
+    let pos = cursor.position();
+    assert!(read_frame(&mut cursor, &config()).unwrap().is_none());
+    assert_eq!(cursor.position(), pos);
+
+    let mut reader = IoReader::new(OneByte(Cursor::new(data)));
+    assert_eq!(decode_frame(&mut reader, &config()).unwrap().unwrap().2, args(1));
+    assert_eq!(decode_frame(&mut reader, &config()).unwrap().unwrap().2, args(2));
+    assert!(reader.is_eof().unwrap());
+}
+
+/// hands out one byte per read
+struct OneByte(Cursor<Vec<u8>>);
+
+impl std::io::Read for OneByte {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
@@ -0,0 +1,99 @@
+use std::io::{Read, Write};
+use rua_proto::{Value, TYPE_STR};
+use rua_proto::config::Config;
+use rua_proto::decode::{decode_proto, decode_str_raw};
+use rua_proto::encode::encode_proto;
+use rua_proto::error::{ErrorKind, RpError};
+use rua_proto::proto_io::ProtoRead;
+use rua_proto::ring::RingBuffer;
+
+fn config() -> Config {
+    Config::new(r#"{}"#, r#"{"cmd_say": {"msg_type": "server", "args": ["str", "u32"]}}"#).unwrap()
+}
+
+/// leave the read and write positions at `pos` so the next writes wrap
+fn wrapped(capacity: usize, pos: usize) -> RingBuffer {
+    let mut ring = RingBuffer::new(capacity);
+    ring.write_all(&vec![0; pos]).unwrap();
+    ring.advance(pos).unwrap();
+    ring
+}
+
+#[test]
+fn test_write_and_read_across_the_wrap() {
+    let mut ring = wrapped(8, 6);
+    ring.write_all(&[1, 2, 3, 4, 5]).unwrap();
+    assert_eq!(ring.as_slices(), (&[1, 2][..], &[3, 4, 5][..]));
+    assert_eq!(ring.peek(2), Some(&[1, 2][..]));
+    assert_eq!(ring.peek(3), None);
+
+    let mut out = [0; 4];
+    ring.read_fill(&mut out).unwrap();
+    assert_eq!(out, [1, 2, 3, 4]);
+    let mut rest = Vec::new();
+    ring.read_to_end(&mut rest).unwrap();
+    assert_eq!(rest, vec![5]);
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn test_make_contiguous_after_wrap() {
+    let mut ring = wrapped(8, 5);
+    ring.write_all(&[1, 2, 3, 4, 5, 6]).unwrap();
+    let read_pos = ring.get_read_pos();
+    assert_eq!(ring.make_contiguous(), &[1, 2, 3, 4, 5, 6]);
+    assert_eq!(ring.as_slices(), (&[1, 2, 3, 4, 5, 6][..], &[][..]));
+    assert_eq!(ring.remaining(), 6);
+    assert!(ring.get_read_pos() >= read_pos);
+    assert_eq!(ring.peek(6), Some(&[1, 2, 3, 4, 5, 6][..]));
+
+    ring.write_all(&[7, 8]).unwrap();
+    let mut out = [0; 8];
+    ring.read_fill(&mut out).unwrap();
+    assert_eq!(out, [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn test_full_buffer_rejects_write() {
+    let mut ring = wrapped(8, 3);
+    ring.write_all(&[1; 6]).unwrap();
+    let err = ring.write(&[2; 3]).err().unwrap();
+    let err = err.into_inner().unwrap().downcast::<RpError>().unwrap();
+    assert_eq!(err.kind(), ErrorKind::BufferOverMaxError);
+    assert_eq!(ring.remaining(), 6);
+    assert_eq!(ring.free_space(), 2);
+
+    ring.write_all(&[2; 2]).unwrap();
+    assert_eq!(ring.free_space(), 0);
+    assert!(ring.write(&[3]).is_err());
+}
+
+#[test]
+fn test_rollback_across_the_wrap() {
+    let config = config();
+    let args = vec![Value::Str("hello".to_string()), Value::U32(9)];
+    let mut encoded = rua_proto::Buffer::new();
+    encode_proto(&mut encoded, &config, &"cmd_say".to_string(), args.clone()).unwrap();
+    let data = encoded.get_data();
+
+    let mut ring = wrapped(32, 32 - 4);
+    ring.write_all(&data[..data.len() - 1]).unwrap();
+    let read_pos = ring.get_read_pos();
+    assert_eq!(decode_proto(&mut ring, &config).err().unwrap().kind(), ErrorKind::IncompleteError);
+    assert_eq!(ring.get_read_pos(), read_pos);
+
+    let mark = ring.mark();
+    ring.advance(6).unwrap();
+    ring.reset(mark);
+    assert_eq!(ring.get_read_pos(), read_pos);
+    {
+        let mut checkpoint = ring.checkpoint();
+        let name = decode_str_raw(&mut *checkpoint, &config, TYPE_STR).unwrap();
+        assert_eq!(name, Value::Str("cmd_say".to_string()));
+    }
+    assert_eq!(ring.get_read_pos(), read_pos);
+
+    ring.write_all(&data[data.len() - 1..]).unwrap();
+    assert_eq!(decode_proto(&mut ring, &config).unwrap(), ("cmd_say".to_string(), args));
+    assert!(ring.is_empty());
+}
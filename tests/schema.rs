@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::decode_proto;
+use rua_proto::encode::encode_proto;
+use rua_proto::error::ErrorKind;
+
+const COLOR: &str = r#"{"pattern": "u8", "variants": {"Red": 1, "Blue": 2}}"#;
+const EFFECT: &str = r#"{"arms": {"heal": {"tag": 1, "pattern": "u32"}, "buff": {"tag": 2, "pattern": "Hero"}, "maybe": {"tag": 3, "pattern": "u32?"}}}"#;
+const HERO: &str = r#"{"fields": {"id": {"index": 1, "pattern": "u32"}, "name": {"index": 2, "pattern": "str"}, "color": {"index": 3, "pattern": "Color"}}}"#;
+
+fn schema(args: &str, color: &str, effect: &str, hero: &str) -> Config {
+    try_schema(args, color, effect, hero).unwrap()
+}
+
+fn try_schema(args: &str, color: &str, effect: &str, hero: &str) -> Option<Config> {
+    Config::new_from_full_str(&format!(r#"{{
+        "field": {{"hp": {{"index": 1, "pattern": "u32"}}, "color": {{"index": 2, "pattern": "Color"}}}},
+        "proto": {{"cmd_test": {{"msg_type": "server", "args": {}}}}},
+        "enum": {{"Color": {}}},
+        "union": {{"Effect": {}}},
+        "struct": {{"Hero": {}}}
+    }}"#, args, color, effect, hero))
+}
+
+fn config(args: &str) -> Config {
+    schema(args, COLOR, EFFECT, HERO)
+}
+
+fn encode(config: &Config, args: Vec<Value>) -> Result<Buffer, ErrorKind> {
+    let mut buffer = Buffer::new();
+    match encode_proto(&mut buffer, config, &"cmd_test".to_string(), args) {
+        Ok(()) => Ok(buffer),
+        Err(err) => Err(err.kind()),
+    }
+}
+
+fn roundtrip(config: &Config, args: Vec<Value>) -> Vec<Value> {
+    let mut buffer = encode(config, args).unwrap();
+    decode_proto(&mut buffer, config).unwrap().1
+}
+
+/// encode by the sender's config and decode by the receiver's, the receiver's
+/// buffer is left untouched on failure
+fn decode_err(sender: &Config, receiver: &Config, args: Vec<Value>) -> ErrorKind {
+    let mut buffer = encode(sender, args).unwrap();
+    let err = decode_proto(&mut buffer, receiver).err().unwrap();
+    assert_eq!(buffer.get_read_pos(), 0);
+    err.kind()
+}
+
+fn color(number: u32, name: &str) -> Value {
+    Value::Enum(number, name.to_string())
+}
+
+fn map(values: Vec<(&str, Value)>) -> Value {
+    Value::Map(values.into_iter().map(|(name, value)| (name.to_string(), value)).collect::<HashMap<_, _>>())
+}
+
+#[test]
+fn test_enum_roundtrip() {
+    let config = config(r#"["Color", "map"]"#);
+    let value = roundtrip(&config, vec![color(2, "Blue"), map(vec![("color", color(1, "Red"))])]);
+    assert_eq!(value, vec![color(2, "Blue"), map(vec![("color", color(1, "Red"))])]);
+
+    // the bare number is accepted and decoded with its name
+    let value = roundtrip(&config, vec![Value::U8(1), map(vec![])]);
+    assert_eq!(value[0], color(1, "Red"));
+}
+
+#[test]
+fn test_enum_unknown_variant_rejected() {
+    let config = config(r#"["Color", "map"]"#);
+    assert_eq!(encode(&config, vec![Value::U8(3), map(vec![])]).err(), Some(ErrorKind::ParseError));
+    assert_eq!(encode(&config, vec![color(2, "Red"), map(vec![])]).err(), Some(ErrorKind::TypeNotMatchError));
+
+    let sender = schema(r#"["Color", "map"]"#, r#"{"pattern": "u8", "variants": {"Red": 1, "Blue": 2, "Green": 3}}"#, EFFECT, HERO);
+    assert_eq!(decode_err(&sender, &config, vec![color(3, "Green"), map(vec![])]), ErrorKind::ParseError);
+    assert_eq!(decode_err(&sender, &config, vec![color(1, "Red"), map(vec![("color", color(3, "Green"))])]),
+               ErrorKind::ParseError);
+}
+
+fn hero(id: u32) -> Value {
+    map(vec![("id", Value::U32(id)), ("name", Value::Str("hero".to_string()))])
+}
+
+#[test]
+fn test_invalid_enum_rejected_at_load() {
+    let alias = r#"{"pattern": "u8", "variants": {"Red": 1, "Crimson": 1}}"#;
+    assert!(try_schema(r#"["Color"]"#, alias, EFFECT, HERO).is_none());
+    let out_of_range = r#"{"pattern": "u8", "variants": {"Red": 1, "Big": 300}}"#;
+    assert!(try_schema(r#"["Color"]"#, out_of_range, EFFECT, HERO).is_none());
+    let str_base = r#"{"pattern": "str", "variants": {"Red": 1}}"#;
+    assert!(try_schema(r#"["Color"]"#, str_base, EFFECT, HERO).is_none());
+
+    let wide = r#"{"pattern": "u16", "variants": {"Red": 1, "Big": 300}}"#;
+    let config = schema(r#"["Color", "map"]"#, wide, EFFECT, HERO);
+    assert_eq!(roundtrip(&config, vec![color(300, "Big"), map(vec![])])[0], color(300, "Big"));
+}
+
+#[test]
+fn test_union_roundtrip() {
+    let config = config(r#"["Effect", "Effect"]"#);
+    let heal = Value::Union(1, "heal".to_string(), Box::new(Value::U32(30)));
+    let buff = Value::Union(2, "buff".to_string(), Box::new(hero(7)));
+    assert_eq!(roundtrip(&config, vec![heal.clone(), buff.clone()]), vec![heal, buff]);
+}
+
+#[test]
+fn test_union_unknown_tag_rejected() {
+    let config = config(r#"["Effect"]"#);
+    let curse = Value::Union(4, "curse".to_string(), Box::new(Value::U32(1)));
+    assert_eq!(encode(&config, vec![curse.clone()]).err(), Some(ErrorKind::ParseError));
+    // the tag must belong to the arm
+    let heal = Value::Union(2, "heal".to_string(), Box::new(Value::U32(1)));
+    assert_eq!(encode(&config, vec![heal]).err(), Some(ErrorKind::TypeNotMatchError));
+
+    let sender = schema(r#"["Effect"]"#, COLOR,
+                        r#"{"arms": {"heal": {"tag": 1, "pattern": "u32"}, "curse": {"tag": 4, "pattern": "u32"}}}"#, HERO);
+    assert_eq!(decode_err(&sender, &config, vec![curse]), ErrorKind::ParseError);
+}
+
+#[test]
+fn test_duplicate_union_tag_rejected_at_load() {
+    let effect = r#"{"arms": {"heal": {"tag": 1, "pattern": "u32"}, "hurt": {"tag": 1, "pattern": "u32"}}}"#;
+    assert!(try_schema(r#"["Effect"]"#, COLOR, effect, HERO).is_none());
+}
+
+#[test]
+fn test_union_payload_mismatch_rejected() {
+    let config = config(r#"["Effect"]"#);
+    let heal = Value::Union(1, "heal".to_string(), Box::new(Value::Str("much".to_string())));
+    assert_eq!(encode(&config, vec![heal]).err(), Some(ErrorKind::TypeNotMatchError));
+
+    // the same tag carries a str for the sender
+    let sender = schema(r#"["Effect"]"#, COLOR, r#"{"arms": {"heal": {"tag": 1, "pattern": "str"}}}"#, HERO);
+    let heal = Value::Union(1, "heal".to_string(), Box::new(Value::Str("much".to_string())));
+    assert_eq!(decode_err(&sender, &config, vec![heal]), ErrorKind::TypeNotMatchError);
+}
+
+#[test]
+fn test_struct_roundtrip() {
+    let config = config(r#"["Hero", "Hero[]"]"#);
+    let mut full = hero(1);
+    if let Value::Map(ref mut val) = full {
+        val.insert("color".to_string(), color(2, "Blue"));
+    }
+    let args = vec![full, Value::VecMap(vec![hero(2), hero(3)])];
+    assert_eq!(roundtrip(&config, args.clone()), args);
+}
+
+#[test]
+fn test_struct_field_mismatch_rejected() {
+    let config = config(r#"["Hero"]"#);
+    let bad = map(vec![("id", Value::Str("one".to_string()))]);
+    assert_eq!(encode(&config, vec![bad]).err(), Some(ErrorKind::TypeNotMatchError));
+    // the global fields don't belong to the struct
+    assert_eq!(encode(&config, vec![map(vec![("hp", Value::U32(1))])]).err(), Some(ErrorKind::ParseError));
+
+    let sender = schema(r#"["Hero"]"#, COLOR, EFFECT, r#"{"fields": {"id": {"index": 1, "pattern": "str"}}}"#);
+    assert_eq!(decode_err(&sender, &config, vec![map(vec![("id", Value::Str("one".to_string()))])]),
+               ErrorKind::TypeNotMatchError);
+}
+
+#[test]
+fn test_duplicate_struct_index_rejected_at_load() {
+    let hero = r#"{"fields": {"id": {"index": 1, "pattern": "u32"}, "name": {"index": 1, "pattern": "str"}}}"#;
+    assert!(try_schema(r#"["Hero"]"#, COLOR, EFFECT, hero).is_none());
+}
+
+#[test]
+fn test_struct_unknown_index_skipped() {
+    let config = config(r#"["Hero"]"#);
+    let sender = schema(r#"["Hero"]"#, COLOR, EFFECT,
+                        r#"{"fields": {"id": {"index": 1, "pattern": "u32"}, "tags": {"index": 9, "pattern": "str[]"}}}"#);
+    let mut buffer = encode(&sender, vec![map(vec![("id", Value::U32(4)),
+                                                   ("tags", Value::VecStr(vec![Value::Str("new".to_string())]))])]).unwrap();
+    assert_eq!(decode_proto(&mut buffer, &config).unwrap().1, vec![map(vec![("id", Value::U32(4))])]);
+}
+
+#[test]
+fn test_legacy_config_has_no_schema() {
+    let field = r#"{"hp": {"index": 1, "pattern": "u32"}}"#;
+    let proto = r#"{"cmd_test": {"msg_type": "server", "args": ["map"]}}"#;
+    let config = Config::new(field, proto).unwrap();
+    assert!(config.get_struct_by_name(&"Hero".to_string()).is_none());
+    assert_eq!(roundtrip(&config, vec![map(vec![("hp", Value::U32(3))])]), vec![map(vec![("hp", Value::U32(3))])]);
+}
+
+#[test]
+fn test_nullable_roundtrip() {
+    let config = config(r#"["u32?", "Hero?", "u8?[]", "Hero?[]", "Effect", "str"]"#);
+    let args = vec![Value::Null,
+                    Value::Null,
+                    Value::VecU8(vec![Value::U8(1), Value::Null, Value::U8(2)]),
+                    Value::VecMap(vec![Value::Null, hero(1)]),
+                    Value::Union(3, "maybe".to_string(), Box::new(Value::Null)),
+                    Value::Str("last".to_string())];
+    assert_eq!(roundtrip(&config, args.clone()), args);
+
+    let args = vec![Value::U32(3), hero(2), Value::VecU8(vec![]), Value::VecMap(vec![]),
+                    Value::Union(3, "maybe".to_string(), Box::new(Value::U32(4))), Value::Str("".to_string())];
+    assert_eq!(roundtrip(&config, args.clone()), args);
+}
+
+#[test]
+fn test_null_in_non_nullable_list_rejected() {
+    let receiver = config(r#"["u8[]", "Hero[]"]"#);
+    let with_null = vec![Value::VecU8(vec![Value::U8(1), Value::Null]), Value::VecMap(vec![])];
+    assert_eq!(encode(&receiver, with_null.clone()).err(), Some(ErrorKind::TypeNotMatchError));
+    let with_null_hero = vec![Value::VecU8(vec![]), Value::VecMap(vec![hero(1), Value::Null])];
+    assert_eq!(encode(&receiver, with_null_hero.clone()).err(), Some(ErrorKind::TypeNotMatchError));
+
+    let sender = config(r#"["u8?[]", "Hero?[]"]"#);
+    assert_eq!(decode_err(&sender, &receiver, with_null), ErrorKind::TypeNotMatchError);
+    assert_eq!(decode_err(&sender, &receiver, with_null_hero), ErrorKind::TypeNotMatchError);
+}
+
+#[test]
+fn test_null_struct_rejected_without_nullable_pattern() {
+    let receiver = config(r#"["Hero"]"#);
+    assert_eq!(encode(&receiver, vec![Value::Null]).err(), Some(ErrorKind::TypeNotMatchError));
+
+    let sender = config(r#"["Hero?"]"#);
+    assert_eq!(decode_err(&sender, &receiver, vec![Value::Null]), ErrorKind::TypeNotMatchError);
+}
+
+#[test]
+fn test_null_scalar_rejected_without_nullable_pattern() {
+    let receiver = config(r#"["u32", "str"]"#);
+    assert_eq!(encode(&receiver, vec![Value::Null, Value::Str("a".to_string())]).err(),
+               Some(ErrorKind::TypeNotMatchError));
+    assert_eq!(encode(&receiver, vec![Value::U32(1), Value::Null]).err(), Some(ErrorKind::TypeNotMatchError));
+    assert_eq!(encode(&receiver, vec![Value::Null, Value::Null]).err(), Some(ErrorKind::TypeNotMatchError));
+
+    let sender = config(r#"["u32?", "str?"]"#);
+    assert_eq!(decode_err(&sender, &receiver, vec![Value::Null, Value::Str("a".to_string())]),
+               ErrorKind::TypeNotMatchError);
+    assert_eq!(decode_err(&sender, &receiver, vec![Value::U32(1), Value::Null]), ErrorKind::TypeNotMatchError);
+    assert_eq!(roundtrip(&sender, vec![Value::Null, Value::Null]), vec![Value::Null, Value::Null]);
+}
+
+#[test]
+fn test_null_map_field_rejected_without_nullable_pattern() {
+    let config = config(r#"["map"]"#);
+    assert_eq!(encode(&config, vec![map(vec![("hp", Value::Null)])]).err(), Some(ErrorKind::TypeNotMatchError));
+}
@@ -0,0 +1,120 @@
+#![cfg(feature = "seal")]
+
+use std::collections::HashMap;
+use std::io::Write;
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::error::ErrorKind;
+use rua_proto::frame::*;
+use rua_proto::seal::{Sealer, SEAL_TAG_LEN};
+
+const KEY: [u8; 32] = [7; 32];
+
+fn config() -> Config {
+    let field = r#"{"name": {"index": 1, "pattern": "str"}, "hp": {"index": 2, "pattern": "u32"}}"#;
+    let proto = r#"{"cmd_login": {"msg_type": "server", "args": ["str", "map"]}}"#;
+    Config::new(field, proto).unwrap()
+}
+
+fn args() -> Vec<Value> {
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), Value::Str("hero".to_string()));
+    map.insert("hp".to_string(), Value::U32(100));
+    vec![Value::Str("login".to_string()), Value::Map(map)]
+}
+
+fn session(key: [u8; 32], is_server: bool) -> FrameSession {
+    let mut session = FrameSession::new();
+    session.set_sealer(Sealer::new(key, is_server));
+    session
+}
+
+fn encode(session: &mut FrameSession) -> Vec<u8> {
+    let mut buffer = Buffer::new();
+    encode_frame_with(&mut buffer, &config(), session, &"cmd_login".to_string(), args(), Some(3)).unwrap();
+    buffer.get_data().clone()
+}
+
+fn decode(session: &mut FrameSession, data: &[u8]) -> Result<Vec<Value>, ErrorKind> {
+    let mut buffer = Buffer::new();
+    buffer.write_all(data).unwrap();
+    match decode_frame_with(&mut buffer, &config(), session) {
+        Ok(frame) => frame.map(|frame| frame.2).ok_or(ErrorKind::IncompleteError),
+        Err(err) => Err(err.kind()),
+    }
+}
+
+#[test]
+fn test_seal_roundtrip() {
+    let mut client = session(KEY, false);
+    let mut server = session(KEY, true);
+    for _ in 0..3 {
+        let data = encode(&mut client);
+        assert_eq!(decode(&mut server, &data), Ok(args()));
+        let data = encode(&mut server);
+        assert_eq!(decode(&mut client, &data), Ok(args()));
+    }
+    assert_eq!(client.get_sealer().unwrap().get_send_nonce(), 3);
+    assert_eq!(server.get_sealer().unwrap().get_recv_nonce(), 3);
+}
+
+#[test]
+fn test_sealed_payload_hides_plaintext() {
+    let data = encode(&mut session(KEY, false));
+    assert!(!data.windows(4).any(|window| window == b"hero"));
+
+    let mut plain = Buffer::new();
+    encode_frame(&mut plain, &config(), &"cmd_login".to_string(), args(), Some(3)).unwrap();
+    assert_eq!(data.len(), plain.get_data().len() + SEAL_TAG_LEN);
+}
+
+#[test]
+fn test_tampered_frame_rejected() {
+    let data = encode(&mut session(KEY, false));
+    // the length bytes are left alone, they only make the frame look incomplete
+    for byte in 4..data.len() {
+        for bit in 0..8 {
+            let mut corrupted = data.clone();
+            corrupted[byte] ^= 1 << bit;
+            match decode(&mut session(KEY, true), &corrupted) {
+                Err(ErrorKind::SealError) => {}
+                // a flipped sequence flag leaves the frame incomplete
+                Err(ErrorKind::IncompleteError) => assert!(byte == 4 && 1 << bit == FRAME_FLAG_SEQ),
+                // a flipped checksum flag is caught before the frame is opened
+                Err(ErrorKind::ChecksumError) => assert!(byte == 4),
+                result => panic!("byte {} bit {}: {:?}", byte, bit, result),
+            }
+        }
+    }
+}
+
+#[test]
+fn test_replayed_frame_rejected() {
+    let mut client = session(KEY, false);
+    let mut server = session(KEY, true);
+    let data = encode(&mut client);
+    assert_eq!(decode(&mut server, &data), Ok(args()));
+    assert_eq!(decode(&mut server, &data), Err(ErrorKind::SealError));
+}
+
+#[test]
+fn test_own_frame_rejected() {
+    let mut client = session(KEY, false);
+    let data = encode(&mut client);
+    assert_eq!(decode(&mut client, &data), Err(ErrorKind::SealError));
+}
+
+#[test]
+fn test_wrong_key_rejected() {
+    let data = encode(&mut session(KEY, false));
+    assert_eq!(decode(&mut session([8; 32], true), &data), Err(ErrorKind::SealError));
+}
+
+#[test]
+fn test_unsealed_frame_rejected() {
+    let data = encode(&mut FrameSession::new());
+    assert_eq!(decode(&mut session(KEY, true), &data), Err(ErrorKind::SealError));
+
+    let data = encode(&mut session(KEY, false));
+    assert_eq!(decode(&mut FrameSession::new(), &data), Err(ErrorKind::SealError));
+}
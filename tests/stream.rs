@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+use std::io::{self, Cursor, Read, Write};
+use rua_proto::{Buffer, Value};
+use rua_proto::config::Config;
+use rua_proto::decode::{decode_proto, StreamDecoder};
+use rua_proto::encode::encode_proto;
+use rua_proto::error::ErrorKind;
+use rua_proto::stream::{ProtoReader, ProtoWriter};
+
+fn config() -> Config {
+    let field = r#"{"name": {"index": 1, "pattern": "str"}, "hp": {"index": 2, "pattern": "u32"}}"#;
+    let proto = r#"{"cmd_login": {"msg_type": "server", "args": ["str", "map", "u16[]"]},
+                    "cmd_move": {"msg_type": "server", "args": ["i32", "i32"]}}"#;
+    Config::new(field, proto).unwrap()
+}
+
+fn login() -> (String, Vec<Value>) {
+    let mut map = HashMap::new();
+    map.insert("name".to_string(), Value::Str("hero".to_string()));
+    map.insert("hp".to_string(), Value::U32(100));
+    ("cmd_login".to_string(),
+     vec![Value::Str("token".to_string()), Value::Map(map), Value::VecU16(vec![Value::U16(1), Value::U16(2)])])
+}
+
+fn step() -> (String, Vec<Value>) {
+    ("cmd_move".to_string(), vec![Value::I32(-3), Value::I32(7)])
+}
+
+fn encode(messages: &[(String, Vec<Value>)]) -> Vec<u8> {
+    let mut data = vec![];
+    for (name, infos) in messages {
+        encode_proto(&mut data, &config(), name, infos.clone()).unwrap();
+    }
+    data
+}
+
+#[test]
+fn test_decode_proto_restores_on_partial_input() {
+    let data = encode(&[login()]);
+    for len in 0..data.len() {
+        let mut buffer = Buffer::new();
+        buffer.write_all(&data[..len]).unwrap();
+        let err = decode_proto(&mut buffer, &config()).err().unwrap();
+        assert_eq!(err.kind(), ErrorKind::IncompleteError, "len {}", len);
+        assert_eq!(buffer.get_read_pos(), 0);
+    }
+}
+
+#[test]
+fn test_stream_decoder_resumes_after_partial_input() {
+    let data = encode(&[login(), step()]);
+    let mut buffer = Buffer::new();
+    let mut decoder = StreamDecoder::new();
+    let mut messages = vec![];
+    let mut was_pending = false;
+    for byte in data {
+        buffer.write_all(&[byte]).unwrap();
+        if let Some(message) = decoder.decode(&mut buffer, &config()).unwrap() {
+            messages.push(message);
+        }
+        was_pending |= decoder.is_pending();
+    }
+    assert!(was_pending);
+    assert!(!decoder.is_pending());
+    assert_eq!(messages, vec![login(), step()]);
+    assert_eq!(buffer.remaining(), 0);
+}
+
+#[test]
+fn test_stream_decoder_keeps_finished_args() {
+    let data = encode(&[login()]);
+    let mut buffer = Buffer::new();
+    let mut decoder = StreamDecoder::new();
+    // the name and the first arg, the map is cut in the middle
+    buffer.write_all(&data[..30]).unwrap();
+    assert_eq!(decoder.decode(&mut buffer, &config()).unwrap(), None);
+    assert!(decoder.is_pending());
+    let pos = buffer.get_read_pos();
+    assert!(pos > 0 && pos < 30);
+
+    buffer.write_all(&data[30..]).unwrap();
+    assert_eq!(decoder.decode(&mut buffer, &config()).unwrap(), Some(login()));
+
+    decoder.reset();
+    assert!(!decoder.is_pending());
+}
+
+/// hands out at most `len` bytes per read and an `Interrupted` error before each
+struct Trickle {
+    data: Cursor<Vec<u8>>,
+    len: usize,
+    interrupted: bool,
+}
+
+impl Read for Trickle {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.interrupted = !self.interrupted;
+        if self.interrupted {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+        }
+        let len = buf.len().min(self.len);
+        self.data.read(&mut buf[..len])
+    }
+}
+
+#[test]
+fn test_writer_reader_roundtrip() {
+    let mut writer = ProtoWriter::new(vec![]);
+    writer.write_proto(&config(), &login().0, login().1).unwrap();
+    writer.write_proto(&config(), &step().0, step().1).unwrap();
+    writer.flush().unwrap();
+    let data = writer.into_inner();
+    assert_eq!(data.len(), encode(&[login(), step()]).len());
+
+    let mut reader = ProtoReader::new(Trickle { data: Cursor::new(data), len: 3, interrupted: false });
+    assert_eq!(reader.read_proto(&config()).unwrap(), Some(login()));
+    assert_eq!(reader.read_proto(&config()).unwrap(), Some(step()));
+    assert_eq!(reader.read_proto(&config()).unwrap(), None);
+    assert_eq!(reader.read_proto(&config()).unwrap(), None);
+}
+
+#[test]
+fn test_reader_eof_between_and_inside_messages() {
+    let data = encode(&[login(), step()]);
+    let first = encode(&[login()]).len();
+
+    let mut reader = ProtoReader::new(Cursor::new(data[..first].to_vec()));
+    assert_eq!(reader.read_proto(&config()).unwrap(), Some(login()));
+    assert_eq!(reader.read_proto(&config()).unwrap(), None);
+
+    let mut reader = ProtoReader::new(Cursor::new(data[..first + 5].to_vec()));
+    assert_eq!(reader.read_proto(&config()).unwrap(), Some(login()));
+    let err = reader.read_proto(&config()).err().unwrap();
+    assert_eq!(err.kind(), ErrorKind::IoError);
+    assert_eq!(reader.buffered_len(), 5);
+}
+
+#[test]
+fn test_reader_into_inner() {
+    let data = encode(&[login(), step()]);
+    let mut reader = ProtoReader::new(Cursor::new(data.clone()));
+    assert_eq!(reader.read_proto(&config()).unwrap(), Some(login()));
+    assert_eq!(reader.get_ref().get_ref(), &data);
+    let cursor = reader.into_inner();
+    assert_eq!(cursor.position() as usize, data.len());
+}
@@ -0,0 +1,134 @@
+use std::collections::HashMap;
+use std::io::Write;
+use rua_proto::{Buffer, Value, ValueRef};
+use rua_proto::config::Config;
+use rua_proto::decode::decode_proto;
+use rua_proto::decode_ref::decode_proto_ref;
+use rua_proto::encode::encode_proto;
+use rua_proto::view::ProtoView;
+
+fn config() -> Config {
+    Config::new_from_full_str(r#"{
+        "field": {"hp": {"index": 1, "pattern": "u32"}, "name": {"index": 2, "pattern": "str"}},
+        "proto": {"cmd_enter": {"msg_type": "server", "args": ["str", "map", "Hero", "Hero[]", "Effect", "u16[]", "raw"]}},
+        "enum": {"Color": {"pattern": "u8", "variants": {"Red": 1, "Blue": 2}}},
+        "union": {"Effect": {"arms": {"heal": {"tag": 1, "pattern": "u32"}, "name": {"tag": 2, "pattern": "str"}}}},
+        "struct": {"Hero": {"fields": {
+            "id": {"index": 1, "pattern": "u32", "required": true},
+            "title": {"index": 2, "pattern": "str", "default": "nobody"},
+            "color": {"index": 3, "pattern": "Color", "default": "Blue"},
+            "level": {"index": 4, "pattern": "u16?"}
+        }}}
+    }"#).unwrap()
+}
+
+fn hero(id: u32, title: Option<&str>) -> Value {
+    let mut map = HashMap::new();
+    map.insert("id".to_string(), Value::U32(id));
+    if let Some(title) = title {
+        map.insert("title".to_string(), Value::Str(title.to_string()));
+    }
+    Value::Map(map)
+}
+
+fn args() -> Vec<Value> {
+    let mut map = HashMap::new();
+    map.insert("hp".to_string(), Value::U32(30));
+    map.insert("name".to_string(), Value::Str("hero".to_string()));
+    vec![Value::Str("enter".to_string()),
+         Value::Map(map),
+         hero(1, Some("king")),
+         Value::VecMap(vec![hero(2, None), hero(3, Some("knight"))]),
+         Value::Union(2, "name".to_string(), Box::new(Value::Str("fire".to_string()))),
+         Value::VecU16(vec![Value::U16(1), Value::U16(2)]),
+         Value::Raw(vec![1, 2, 3])]
+}
+
+fn encode() -> Vec<u8> {
+    let mut buffer = Buffer::new();
+    encode_proto(&mut buffer, &config(), &"cmd_enter".to_string(), args()).unwrap();
+    buffer.get_data().clone()
+}
+
+#[test]
+fn test_decode_ref_borrows_from_input() {
+    let config = config();
+    let data = encode();
+    let (name, value) = decode_proto_ref(&data, &config).unwrap();
+    assert_eq!(name, "cmd_enter");
+    assert_eq!(value.len(), 7);
+
+    let range = data.as_ptr() as usize..data.as_ptr() as usize + data.len();
+    match value[0] {
+        ValueRef::Str(val) => {
+            assert_eq!(val, "enter");
+            assert!(range.contains(&(val.as_ptr() as usize)));
+        }
+        ref val => panic!("{:?}", val),
+    }
+    match value[6] {
+        ValueRef::Raw(val) => {
+            assert_eq!(val, &[1, 2, 3]);
+            assert!(range.contains(&(val.as_ptr() as usize)));
+        }
+        ref val => panic!("{:?}", val),
+    }
+    match value[2] {
+        ValueRef::Map(ref map) => {
+            assert_eq!(map["id"], ValueRef::U32(1));
+            assert_eq!(map["title"], ValueRef::Str("king"));
+            assert_eq!(map["color"], ValueRef::Enum(2, "Blue"));
+            assert!(!map.contains_key("level"));
+        }
+        ref val => panic!("{:?}", val),
+    }
+    assert_eq!(value[4], ValueRef::Union(2, "name", Box::new(ValueRef::Str("fire"))));
+}
+
+#[test]
+fn test_decode_ref_to_owned_matches_decode_proto() {
+    let config = config();
+    let data = encode();
+    let (name, value) = decode_proto_ref(&data, &config).unwrap();
+
+    let mut buffer = Buffer::new();
+    buffer.write_all(&data).unwrap();
+    let owned = decode_proto(&mut buffer, &config).unwrap();
+    assert_eq!(owned.0, name);
+    assert_eq!(owned.1, value.iter().map(|val| val.to_owned()).collect::<Vec<Value>>());
+}
+
+#[test]
+fn test_decode_ref_incomplete() {
+    let config = config();
+    let data = encode();
+    for len in 0..data.len() {
+        let err = decode_proto_ref(&data[..len], &config).err().unwrap();
+        assert!(err.is_incomplete(), "len {}: {:?}", len, err);
+    }
+}
+
+#[test]
+fn test_view_get_field() {
+    let config = config();
+    let data = encode();
+    let view = ProtoView::new(&data, &config).unwrap();
+    assert_eq!(view.get_name(), "cmd_enter");
+
+    let map = view.get_arg(1).unwrap().unwrap();
+    assert_eq!(map.get_field("hp").unwrap().unwrap().decode().unwrap(), ValueRef::U32(30));
+    assert_eq!(map.get_field("name").unwrap().unwrap().decode().unwrap(), ValueRef::Str("hero"));
+    assert!(map.get_field("unknown").unwrap().is_none());
+
+    let hero = view.get_arg(2).unwrap().unwrap();
+    // present
+    assert_eq!(hero.get_field("title").unwrap().unwrap().decode().unwrap(), ValueRef::Str("king"));
+    // absent without a default
+    assert!(hero.get_field("level").unwrap().is_none());
+    // absent with a default, only the decoded map carries it
+    assert!(hero.get_field("color").unwrap().is_none());
+    match hero.decode().unwrap() {
+        ValueRef::Map(ref map) => assert_eq!(map["color"], ValueRef::Enum(2, "Blue")),
+        ref val => panic!("{:?}", val),
+    }
+}